@@ -0,0 +1,247 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::{exit, Command};
+
+use snapshot_testing::{
+    compare_text, find_pending_snapshots, find_unreferenced_snapshots, hex_diff, Color, Metadata, PendingSnapshot,
+    Settings, MANIFEST_DIR_VAR,
+};
+
+const USAGE: &str = "\
+Usage: cargo snapshot <command>
+
+Commands:
+    review              Review each pending snapshot interactively
+    accept --all        Accept all pending snapshots
+    accept <snapshot>…  Accept the pending changes to the given snapshots
+    reject --all        Reject all pending snapshots
+    reject <snapshot>…  Reject the pending changes to the given snapshots
+    prune [--delete] [-- <cargo test args>…]
+                        Run `cargo test` and list the snapshots no test referenced. With
+                        `--delete`, delete those of tests which ran; others are only listed";
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // When run as `cargo snapshot`, cargo passes the subcommand name as the first argument.
+    if args.first().map(String::as_str) == Some("snapshot") {
+        args.remove(0);
+    }
+    // The project configuration is read from the crate being tested, which is the current one.
+    if std::env::var_os("CARGO_MANIFEST_DIR").is_none() {
+        if let Ok(dir) = std::env::current_dir() {
+            std::env::set_var("CARGO_MANIFEST_DIR", dir);
+        }
+    }
+    let result = match args.split_first() {
+        Some((command, rest)) if command == "review" && rest.is_empty() => review(),
+        Some((command, rest)) if command == "accept" && !rest.is_empty() => {
+            for_each_selected(rest, "Accepted", PendingSnapshot::accept)
+        }
+        Some((command, rest)) if command == "reject" && !rest.is_empty() => {
+            for_each_selected(rest, "Rejected", PendingSnapshot::reject)
+        }
+        Some((command, rest)) if command == "prune" => match rest.split_first() {
+            Some((flag, rest)) if flag == "--delete" => prune(rest, true),
+            _ => prune(rest, false),
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
+
+fn review() -> io::Result<()> {
+    let pending = find_pending_snapshots(".")?;
+    if pending.is_empty() {
+        println!("No pending snapshots");
+        return Ok(());
+    }
+    let (mut accepted, mut rejected, mut skipped) = (0, 0, 0);
+    let mut lines = io::stdin().lock().lines();
+    for snapshot in &pending {
+        show(snapshot)?;
+        loop {
+            print!("[a]ccept, [r]eject or [s]kip? ");
+            io::stdout().flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+            match line.trim() {
+                "a" | "accept" => {
+                    snapshot.accept()?;
+                    accepted += 1;
+                }
+                "r" | "reject" => {
+                    snapshot.reject()?;
+                    rejected += 1;
+                }
+                "s" | "skip" => skipped += 1,
+                _ => continue,
+            }
+            break;
+        }
+    }
+    println!("{} accepted, {} rejected, {} skipped", accepted, rejected, skipped);
+    Ok(())
+}
+
+/// Prints the changes a pending snapshot makes: a text diff, a hex diff for binary snapshots, or
+/// for images their sizes and the image highlighting the differing pixels.
+fn show(snapshot: &PendingSnapshot) -> io::Result<()> {
+    let new = std::fs::read(&snapshot.pending)?;
+    let old = if snapshot.snapshot.exists() {
+        println!("Pending changes to {}:", snapshot.snapshot.display());
+        Some(std::fs::read(&snapshot.snapshot)?)
+    } else {
+        println!("New snapshot {}:", snapshot.snapshot.display());
+        None
+    };
+
+    if snapshot.snapshot.extension() == Some("png".as_ref()) {
+        match &old {
+            Some(old) => println!("image of {} bytes, was {} bytes", new.len(), old.len()),
+            None => println!("image of {} bytes", new.len()),
+        }
+        let diff_image = snapshot.snapshot.with_extension("diff.png");
+        if diff_image.exists() {
+            println!("The differing pixels are highlighted in {}", diff_image.display());
+        }
+        return Ok(());
+    }
+    let (old, new) = match (old.as_deref().map(text), text(&new)) {
+        (Some(Some(old)), Some(new)) => (old, new),
+        (None, Some(new)) => {
+            println!("{}", new);
+            return Ok(());
+        }
+        _ => {
+            print!("{}", hex_diff(old.as_deref().unwrap_or_default(), &new));
+            return Ok(());
+        }
+    };
+    let mut options = Settings::from_env().compare_options().clone();
+    if options.diff.color == Color::Auto && !io::stdout().is_terminal() {
+        options.diff.color = Color::Never;
+    }
+    // Headers aren't compared, and differ whenever the assertion moves.
+    match compare_text(Metadata::parse(old).1, Metadata::parse(new).1, &options) {
+        Ok(()) => println!("(no changes)"),
+        Err(diff) => print!("{}", diff),
+    }
+    Ok(())
+}
+
+/// Returns `bytes` as text, unless they aren't UTF-8 or contain control characters other than
+/// whitespace, which would be invisible in a text diff.
+fn text(bytes: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    if text.chars().any(|c| c.is_control() && !c.is_ascii_whitespace()) {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Runs `cargo test` with `args`, recording the snapshots each test binary references, and then
+/// lists or deletes the unreferenced snapshots. Only stale snapshots, of tests which ran, are
+/// deleted. Listing exits with an error if there are any.
+fn prune(args: &[String], delete: bool) -> io::Result<()> {
+    let args = match args.split_first() {
+        Some((separator, rest)) if separator == "--" => rest,
+        None => args,
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+    if delete && has_test_filter(args) {
+        eprintln!("error: not deleting snapshots when tests are filtered, as the skipped tests' snapshots look unused");
+        exit(2);
+    }
+    let manifest_dir = std::env::current_dir()?.join("target/snapshot-testing/manifests");
+    if manifest_dir.exists() {
+        std::fs::remove_dir_all(&manifest_dir)?;
+    }
+    std::fs::create_dir_all(&manifest_dir)?;
+
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .arg("test")
+        .args(args)
+        .env(MANIFEST_DIR_VAR, &manifest_dir)
+        .status()?;
+    if !status.success() {
+        eprintln!("error: tests failed, so referenced snapshots may be missing; not pruning");
+        exit(1);
+    }
+
+    let unreferenced = find_unreferenced_snapshots(&manifest_dir)?;
+    for snapshot in &unreferenced.stale {
+        if delete {
+            std::fs::remove_file(snapshot)?;
+            println!("Deleted {}", snapshot.display());
+        } else {
+            println!("Unreferenced {}", snapshot.display());
+        }
+    }
+    for snapshot in &unreferenced.unknown {
+        println!("Possibly unreferenced {} (no test named after it ran)", snapshot.display());
+    }
+    if !delete && !unreferenced.stale.is_empty() {
+        exit(1);
+    }
+    Ok(())
+}
+
+/// Whether the `cargo test` arguments `args` select a subset of the tests: a test name filter,
+/// or any of the test harness's filtering flags.
+fn has_test_filter(args: &[String]) -> bool {
+    const CARGO_OPTIONS_WITH_VALUES: &[&str] = &[
+        "-p", "--package", "--exclude", "-F", "--features", "--test", "--bin", "--example", "--bench", "--target",
+        "--target-dir", "--manifest-path", "--profile", "-j", "--jobs", "--color", "--message-format", "--config",
+        "-Z",
+    ];
+    const HARNESS_FILTERS: &[&str] = &["--skip", "--ignored", "--exact"];
+
+    let (cargo_args, harness_args) = match args.iter().position(|arg| arg == "--") {
+        Some(separator) => (&args[..separator], &args[separator + 1..]),
+        None => (args, &[][..]),
+    };
+    let mut cargo_args = cargo_args.iter();
+    while let Some(arg) = cargo_args.next() {
+        if CARGO_OPTIONS_WITH_VALUES.contains(&arg.as_str()) {
+            cargo_args.next();
+        } else if !arg.starts_with('-') {
+            return true;
+        }
+    }
+    harness_args
+        .iter()
+        .any(|arg| !arg.starts_with('-') || HARNESS_FILTERS.contains(&arg.split('=').next().unwrap_or(arg)))
+}
+
+/// Runs `action` on every pending snapshot if `args` is `--all`, or else on the pending snapshots
+/// for the snapshot (or pending snapshot) paths in `args`.
+fn for_each_selected(
+    args: &[String],
+    verb: &str,
+    action: fn(&PendingSnapshot) -> io::Result<()>,
+) -> io::Result<()> {
+    let selected = if args == ["--all"] {
+        find_pending_snapshots(".")?
+    } else {
+        args.iter()
+            .map(|arg| PendingSnapshot::new(arg.strip_suffix(".new").unwrap_or(arg)))
+            .collect()
+    };
+    for snapshot in &selected {
+        action(snapshot)?;
+        println!("{} {}", verb, snapshot.snapshot.display());
+    }
+    Ok(())
+}
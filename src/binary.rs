@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::{check_contents, Contents, Error};
+
+const ROW_LEN: usize = 16;
+
+pub(crate) struct Binary<'a>(pub &'a [u8]);
+
+impl Contents for Binary<'_> {
+    fn contents(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0)
+    }
+
+    fn compare(&self, expected: &[u8], snapshot: &Path, show_diff: bool) -> Result<(), Error> {
+        if self.0 == expected {
+            Ok(())
+        } else {
+            if show_diff {
+                eprintln!("{}", hex_diff(expected, self.0));
+            }
+            Err(Error::Difference {
+                path: snapshot.to_owned(),
+                diff: None,
+            })
+        }
+    }
+}
+
+pub fn check_binary_snapshot(actual: &[u8], snapshot: impl AsRef<Path>) -> Result<(), Error> {
+    check_contents(&Binary(actual), snapshot.as_ref(), true)
+}
+
+pub fn check_binary_snapshot_no_diff(actual: &[u8], snapshot: impl AsRef<Path>) -> Result<(), Error> {
+    check_contents(&Binary(actual), snapshot.as_ref(), false)
+}
+
+/// Renders the rows of a hex dump of `expected` and `actual` which differ, aligned by offset.
+/// Runs of identical rows are elided.
+pub fn hex_diff(expected: &[u8], actual: &[u8]) -> String {
+    let mut diff = format!("expected {} bytes, actual {} bytes\n", expected.len(), actual.len());
+    let rows = expected.len().max(actual.len()).div_ceil(ROW_LEN);
+    let mut elided = false;
+    for row in 0..rows {
+        let (expected_row, actual_row) = (row_bytes(expected, row), row_bytes(actual, row));
+        if expected_row == actual_row {
+            elided = true;
+            continue;
+        }
+        if elided {
+            diff.push_str("  ...\n");
+            elided = false;
+        }
+        if !expected_row.is_empty() {
+            writeln!(diff, "-{}", hex_row(row * ROW_LEN, expected_row)).unwrap();
+        }
+        if !actual_row.is_empty() {
+            writeln!(diff, "+{}", hex_row(row * ROW_LEN, actual_row)).unwrap();
+        }
+    }
+    if elided {
+        diff.push_str("  ...\n");
+    }
+    diff
+}
+
+fn row_bytes(bytes: &[u8], row: usize) -> &[u8] {
+    let start = (row * ROW_LEN).min(bytes.len());
+    &bytes[start..(start + ROW_LEN).min(bytes.len())]
+}
+
+/// Formats a row of a hex dump, e.g.
+/// `00000010  68 65 6c 6c 6f 0a                                |hello.|`
+fn hex_row(offset: usize, bytes: &[u8]) -> String {
+    let mut row = format!("{:08x} ", offset);
+    for i in 0..ROW_LEN {
+        if i % 8 == 0 {
+            row.push(' ');
+        }
+        match bytes.get(i) {
+            Some(byte) => write!(row, "{:02x} ", byte).unwrap(),
+            None => row.push_str("   "),
+        }
+    }
+    row.push_str(" |");
+    row.extend(bytes.iter().map(|&byte| {
+        if byte.is_ascii_graphic() || byte == b' ' {
+            byte as char
+        } else {
+            '.'
+        }
+    }));
+    row.push('|');
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hex_diff;
+
+    #[test]
+    fn diff() {
+        let expected: Vec<u8> = (0..64).collect();
+        let mut actual = expected.clone();
+        actual[20] = b'A';
+        actual.extend_from_slice(b"\xffend");
+        assert_eq!(
+            hex_diff(&expected, &actual),
+            "expected 64 bytes, actual 68 bytes
+  ...
+-00000010  10 11 12 13 14 15 16 17  18 19 1a 1b 1c 1d 1e 1f  |................|
++00000010  10 11 12 13 41 15 16 17  18 19 1a 1b 1c 1d 1e 1f  |....A...........|
+  ...
++00000040  ff 65 6e 64                                       |.end|
+"
+        );
+    }
+
+    #[test]
+    fn binary_snapshot() {
+        super::check_binary_snapshot(b"\x00\x01binary\xff", "snapshots/binary.bin").unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use toml::value::Table;
+use toml::Value;
+
+use crate::{FinalNewline, Filters, Settings, UpdateMode};
+
+/// The name of the project configuration file, which is looked for in the crate's manifest
+/// directory. Without one, the configuration is read from `[package.metadata.snapshot-testing]`
+/// in `Cargo.toml`.
+///
+/// ```toml
+/// snapshot-dir = "tests/snapshots"
+/// update = "no"
+/// diff = "histogram,context=5"
+///
+/// [normalize]
+/// line-endings = true
+/// trailing-whitespace = true
+/// final-newline = "require"
+///
+/// # Applied in order to every text snapshot, before it is compared or written.
+/// [[filters]]
+/// regex = 'took \d+ms'
+/// replacement = "took [time]"
+/// ```
+pub const CONFIG_FILE: &str = "snapshot.toml";
+
+/// Returns the settings of the project configuration in `CARGO_MANIFEST_DIR`, which is loaded
+/// once per process.
+///
+/// # Panics
+///
+/// Panics if the configuration can't be read or is invalid.
+pub(crate) fn project_settings() -> &'static Settings {
+    static SETTINGS: OnceLock<Settings> = OnceLock::new();
+    SETTINGS.get_or_init(|| match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(dir) => load(Path::new(&dir)).unwrap_or_else(|err| panic!("{}", err)),
+        None => Settings::default(),
+    })
+}
+
+fn load(manifest_dir: &Path) -> Result<Settings, String> {
+    let config_file = manifest_dir.join(CONFIG_FILE);
+    let (path, config) = if config_file.exists() {
+        let config = read(&config_file)?;
+        (config_file, config)
+    } else {
+        let manifest = manifest_dir.join("Cargo.toml");
+        if !manifest.exists() {
+            return Ok(Settings::default());
+        }
+        let config = read(&manifest)?
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("snapshot-testing"))
+            .cloned();
+        match config {
+            Some(config) => (manifest, config),
+            None => return Ok(Settings::default()),
+        }
+    };
+    let config = config.as_table().ok_or_else(|| "expected a table".to_owned());
+    config
+        .and_then(parse)
+        .map_err(|message| format!("Invalid snapshot configuration in {}: {}", path.display(), message))
+}
+
+fn read(path: &Path) -> Result<Value, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("Error reading {}: {}", path.display(), err))?;
+    text.parse().map_err(|err| format!("Error parsing {}: {}", path.display(), err))
+}
+
+fn parse(config: &Table) -> Result<Settings, String> {
+    let mut settings = Settings::default();
+    for (key, value) in config {
+        match key.as_str() {
+            "snapshot-dir" => settings.snapshot_dir = string(key, value)?.into(),
+            "update" => {
+                let mode = string(key, value)?;
+                settings.update_mode =
+                    UpdateMode::parse(mode).ok_or_else(|| format!("unknown update mode `{}`", mode))?;
+            }
+            "diff" => settings.compare.diff = settings.compare.diff.parse(string(key, value)?),
+            "normalize" => {
+                let table = value.as_table().ok_or_else(|| format!("`{}` should be a table", key))?;
+                for (key, value) in table {
+                    let normalize = &mut settings.compare.normalize;
+                    match key.as_str() {
+                        "line-endings" => normalize.line_endings = boolean(key, value)?,
+                        "trailing-whitespace" => normalize.trailing_whitespace = boolean(key, value)?,
+                        "final-newline" => {
+                            normalize.final_newline = match string(key, value)? {
+                                "keep" => FinalNewline::Keep,
+                                "ignore" => FinalNewline::Ignore,
+                                "require" => FinalNewline::Require,
+                                other => return Err(format!("unknown final newline policy `{}`", other)),
+                            }
+                        }
+                        _ => return Err(format!("unknown key `normalize.{}`", key)),
+                    }
+                }
+            }
+            "filters" => {
+                let filters = value.as_array().ok_or_else(|| format!("`{}` should be an array of tables", key))?;
+                settings.compare.filters = filters.iter().try_fold(Filters::new(), |filters, filter| {
+                    let table = filter.as_table().ok_or_else(|| format!("`{}` should be an array of tables", key))?;
+                    if let Some(key) = table.keys().find(|key| *key != "regex" && *key != "replacement") {
+                        return Err(format!("unknown key `filters.{}`", key));
+                    }
+                    let field = |name| table.get(name).ok_or_else(|| format!("missing `filters.{}`", name));
+                    let regex = string("filters.regex", field("regex")?)?;
+                    let replacement = string("filters.replacement", field("replacement")?)?;
+                    filters
+                        .try_add(regex, replacement)
+                        .map_err(|err| format!("invalid filter regex `{}`: {}", regex, err))
+                })?;
+            }
+            _ => return Err(format!("unknown key `{}`", key)),
+        }
+    }
+    Ok(settings)
+}
+
+fn string<'a>(key: &str, value: &'a Value) -> Result<&'a str, String> {
+    value.as_str().ok_or_else(|| format!("`{}` should be a string", key))
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or_else(|| format!("`{}` should be a boolean", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{check_snapshot_with_options, Algorithm, CompareOptions, Error, FinalNewline, Filters, UpdateMode};
+
+    use super::load;
+
+    fn project(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = Path::new("target/config-test").join(name);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn load_config() {
+        let dir = project(
+            "snapshot-toml",
+            &[
+                ("Cargo.toml", "[package.metadata.snapshot-testing]\nupdate = \"always\"\n"),
+                (
+                    "snapshot.toml",
+                    "snapshot-dir = \"tests/snaps\"\nupdate = \"pending\"\ndiff = \"histogram\"\n\
+                     [normalize]\nfinal-newline = \"require\"\n\
+                     [[filters]]\nregex = 'took \\d+ms'\nreplacement = \"took [time]\"\n",
+                ),
+            ],
+        );
+        let settings = load(&dir).unwrap();
+        assert_eq!(settings.snapshot_dir, Path::new("tests/snaps"));
+        assert_eq!(settings.update_mode, UpdateMode::Pending);
+        assert_eq!(settings.compare.diff.algorithm, Algorithm::Histogram);
+        assert_eq!(settings.compare.normalize.final_newline, FinalNewline::Require);
+        assert_eq!(settings.compare.filters.apply("took 17ms"), "took [time]");
+
+        let dir = project(
+            "metadata",
+            &[("Cargo.toml", "[package]\nname = \"x\"\n[package.metadata.snapshot-testing]\nupdate = \"always\"\n")],
+        );
+        assert_eq!(load(&dir).unwrap().update_mode, UpdateMode::Always);
+
+        let dir = project("empty", &[("Cargo.toml", "[package]\nname = \"x\"\n")]);
+        assert_eq!(load(&dir).unwrap().update_mode, UpdateMode::default());
+
+        let dir = project("invalid", &[("snapshot.toml", "update = \"sometimes\"\n")]);
+        let err = load(&dir).unwrap_err();
+        assert!(err.contains("unknown update mode `sometimes`"), "{}", err);
+
+        let dir = project("invalid-filter", &[("snapshot.toml", "[[filters]]\nregex = \"(\"\nreplacement = \"\"\n")]);
+        let err = load(&dir).unwrap_err();
+        assert!(err.contains("invalid filter regex `(`"), "{}", err);
+    }
+
+    #[test]
+    fn config_filters() {
+        let dir = project(
+            "filters",
+            &[(
+                "snapshot.toml",
+                "[normalize]\nfinal-newline = \"require\"\n\
+                 [[filters]]\nregex = 'took \\d+ms'\nreplacement = \"took [time]\"\n",
+            )],
+        );
+        let settings = load(&dir).unwrap();
+        let options = CompareOptions {
+            filters: Filters::new().add(r"\[time\]", "[duration]"),
+            ..CompareOptions::default()
+        };
+        let snapshot = dir.join("filtered.snap");
+        let new = settings.clone().update_mode(UpdateMode::New);
+        match new.bind(|| check_snapshot_with_options("took 3ms", &snapshot, &options)) {
+            Err(Error::Created { .. }) => {}
+            other => panic!("Expected `Err(Created)`, got `{:?}`", other),
+        }
+        assert_eq!(std::fs::read_to_string(&snapshot).unwrap(), "took [duration]\n");
+        settings.bind(|| check_snapshot_with_options("took 250ms\n", &snapshot, &options)).unwrap();
+    }
+}
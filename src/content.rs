@@ -0,0 +1,537 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::ser::{self, Serialize, Serializer};
+
+/// A value in serde's data model, captured so that it can be redacted before it is serialized
+/// in a snapshot format. Unlike `serde_json::Value`, it keeps everything the formats can express:
+/// options, struct and variant names, tuples and non-string map keys.
+///
+/// Maps are sorted by key when they are captured, so that snapshots don't depend on e.g.
+/// `HashMap` iteration order. Struct fields keep their declaration order.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Content {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    String(String),
+    Bytes(Vec<u8>),
+    None,
+    Some(Box<Content>),
+    Unit,
+    UnitStruct(&'static str),
+    UnitVariant(&'static str, u32, &'static str),
+    NewtypeStruct(&'static str, Box<Content>),
+    NewtypeVariant(&'static str, u32, &'static str, Box<Content>),
+    Seq(Vec<Content>),
+    Tuple(Vec<Content>),
+    TupleStruct(&'static str, Vec<Content>),
+    TupleVariant(&'static str, u32, &'static str, Vec<Content>),
+    Map(Vec<(Content, Content)>),
+    Struct(&'static str, Vec<(&'static str, Content)>),
+    StructVariant(&'static str, u32, &'static str, Vec<(&'static str, Content)>),
+}
+
+impl Content {
+    pub fn new(value: &(impl Serialize + ?Sized)) -> Result<Self, ContentError> {
+        value.serialize(ContentSerializer)
+    }
+
+    /// The values nested directly inside this one.
+    pub fn children_mut(&mut self) -> Vec<&mut Content> {
+        match self {
+            Content::Some(inner) | Content::NewtypeStruct(_, inner) | Content::NewtypeVariant(_, _, _, inner) => {
+                vec![&mut **inner]
+            }
+            Content::Seq(items)
+            | Content::Tuple(items)
+            | Content::TupleStruct(_, items)
+            | Content::TupleVariant(_, _, _, items) => items.iter_mut().collect(),
+            Content::Map(entries) => entries.iter_mut().map(|(_, value)| value).collect(),
+            Content::Struct(_, fields) | Content::StructVariant(_, _, _, fields) => {
+                fields.iter_mut().map(|(_, value)| value).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Orders map keys: strings and numbers by value, anything else by its debug representation.
+fn compare_keys(a: &Content, b: &Content) -> Ordering {
+    fn integer(content: &Content) -> Option<i128> {
+        match *content {
+            Content::I64(n) => Some(n.into()),
+            Content::U64(n) => Some(n.into()),
+            Content::I128(n) => Some(n),
+            Content::U128(n) => i128::try_from(n).ok(),
+            _ => None,
+        }
+    }
+
+    match (a, b) {
+        (Content::String(a), Content::String(b)) => a.cmp(b),
+        _ => match (integer(a), integer(b)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => format!("{:?}", a).cmp(&format!("{:?}", b)),
+        },
+    }
+}
+
+impl Serialize for Content {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::{
+            SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleStruct,
+            SerializeTupleVariant,
+        };
+
+        match self {
+            Content::Bool(value) => serializer.serialize_bool(*value),
+            Content::I64(value) => serializer.serialize_i64(*value),
+            Content::U64(value) => serializer.serialize_u64(*value),
+            Content::I128(value) => serializer.serialize_i128(*value),
+            Content::U128(value) => serializer.serialize_u128(*value),
+            Content::F32(value) => serializer.serialize_f32(*value),
+            Content::F64(value) => serializer.serialize_f64(*value),
+            Content::Char(value) => serializer.serialize_char(*value),
+            Content::String(value) => serializer.serialize_str(value),
+            Content::Bytes(value) => serializer.serialize_bytes(value),
+            Content::None => serializer.serialize_none(),
+            Content::Some(inner) => serializer.serialize_some(inner),
+            Content::Unit => serializer.serialize_unit(),
+            Content::UnitStruct(name) => serializer.serialize_unit_struct(name),
+            Content::UnitVariant(name, index, variant) => serializer.serialize_unit_variant(name, *index, variant),
+            Content::NewtypeStruct(name, inner) => serializer.serialize_newtype_struct(name, inner),
+            Content::NewtypeVariant(name, index, variant, inner) => {
+                serializer.serialize_newtype_variant(name, *index, variant, inner)
+            }
+            Content::Seq(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Content::Tuple(items) => {
+                let mut tuple = serializer.serialize_tuple(items.len())?;
+                for item in items {
+                    tuple.serialize_element(item)?;
+                }
+                tuple.end()
+            }
+            Content::TupleStruct(name, items) => {
+                let mut tuple = serializer.serialize_tuple_struct(name, items.len())?;
+                for item in items {
+                    tuple.serialize_field(item)?;
+                }
+                tuple.end()
+            }
+            Content::TupleVariant(name, index, variant, items) => {
+                let mut tuple = serializer.serialize_tuple_variant(name, *index, variant, items.len())?;
+                for item in items {
+                    tuple.serialize_field(item)?;
+                }
+                tuple.end()
+            }
+            Content::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Content::Struct(name, fields) => {
+                let mut state = serializer.serialize_struct(name, fields.len())?;
+                for (key, value) in fields {
+                    state.serialize_field(key, value)?;
+                }
+                state.end()
+            }
+            Content::StructVariant(name, index, variant, fields) => {
+                let mut state = serializer.serialize_struct_variant(name, *index, variant, fields.len())?;
+                for (key, value) in fields {
+                    state.serialize_field(key, value)?;
+                }
+                state.end()
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ContentError(String);
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ContentError {}
+
+impl ser::Error for ContentError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ContentError(message.to_string())
+    }
+}
+
+struct ContentSerializer;
+
+enum SeqKind {
+    Seq,
+    Tuple,
+    TupleStruct(&'static str),
+    TupleVariant(&'static str, u32, &'static str),
+}
+
+struct SeqBuilder {
+    kind: SeqKind,
+    items: Vec<Content>,
+}
+
+impl SeqBuilder {
+    fn push(&mut self, value: &(impl Serialize + ?Sized)) -> Result<(), ContentError> {
+        self.items.push(Content::new(value)?);
+        Ok(())
+    }
+
+    fn build(self) -> Result<Content, ContentError> {
+        Ok(match self.kind {
+            SeqKind::Seq => Content::Seq(self.items),
+            SeqKind::Tuple => Content::Tuple(self.items),
+            SeqKind::TupleStruct(name) => Content::TupleStruct(name, self.items),
+            SeqKind::TupleVariant(name, index, variant) => Content::TupleVariant(name, index, variant, self.items),
+        })
+    }
+}
+
+struct MapBuilder {
+    entries: Vec<(Content, Content)>,
+    key: Option<Content>,
+}
+
+struct StructBuilder {
+    variant: Option<(u32, &'static str)>,
+    name: &'static str,
+    fields: Vec<(&'static str, Content)>,
+}
+
+impl StructBuilder {
+    fn build(self) -> Result<Content, ContentError> {
+        Ok(match self.variant {
+            None => Content::Struct(self.name, self.fields),
+            Some((index, variant)) => Content::StructVariant(self.name, index, variant, self.fields),
+        })
+    }
+}
+
+impl Serializer for ContentSerializer {
+    type Ok = Content;
+    type Error = ContentError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = StructBuilder;
+    type SerializeStructVariant = StructBuilder;
+
+    fn serialize_bool(self, value: bool) -> Result<Content, ContentError> {
+        Ok(Content::Bool(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<Content, ContentError> {
+        Ok(Content::I64(value.into()))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<Content, ContentError> {
+        Ok(Content::I64(value.into()))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<Content, ContentError> {
+        Ok(Content::I64(value.into()))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<Content, ContentError> {
+        Ok(Content::I64(value))
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<Content, ContentError> {
+        Ok(Content::I128(value))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<Content, ContentError> {
+        Ok(Content::U64(value.into()))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<Content, ContentError> {
+        Ok(Content::U64(value.into()))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<Content, ContentError> {
+        Ok(Content::U64(value.into()))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<Content, ContentError> {
+        Ok(Content::U64(value))
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<Content, ContentError> {
+        Ok(Content::U128(value))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<Content, ContentError> {
+        Ok(Content::F32(value))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<Content, ContentError> {
+        Ok(Content::F64(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<Content, ContentError> {
+        Ok(Content::Char(value))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Content, ContentError> {
+        Ok(Content::String(value.to_owned()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Content, ContentError> {
+        Ok(Content::Bytes(value.to_owned()))
+    }
+
+    fn serialize_none(self) -> Result<Content, ContentError> {
+        Ok(Content::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Content, ContentError> {
+        Ok(Content::Some(Box::new(Content::new(value)?)))
+    }
+
+    fn serialize_unit(self) -> Result<Content, ContentError> {
+        Ok(Content::Unit)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Content, ContentError> {
+        Ok(Content::UnitStruct(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<Content, ContentError> {
+        Ok(Content::UnitVariant(name, index, variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Content, ContentError> {
+        Ok(Content::NewtypeStruct(name, Box::new(Content::new(value)?)))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Content, ContentError> {
+        Ok(Content::NewtypeVariant(name, index, variant, Box::new(Content::new(value)?)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, ContentError> {
+        Ok(SeqBuilder {
+            kind: SeqKind::Seq,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, ContentError> {
+        Ok(SeqBuilder {
+            kind: SeqKind::Tuple,
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<SeqBuilder, ContentError> {
+        Ok(SeqBuilder {
+            kind: SeqKind::TupleStruct(name),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ContentError> {
+        Ok(SeqBuilder {
+            kind: SeqKind::TupleVariant(name, index, variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapBuilder, ContentError> {
+        Ok(MapBuilder {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<StructBuilder, ContentError> {
+        Ok(StructBuilder {
+            variant: None,
+            name,
+            fields: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<StructBuilder, ContentError> {
+        Ok(StructBuilder {
+            variant: Some((index, variant)),
+            name,
+            fields: Vec::with_capacity(len),
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = Content;
+    type Error = ContentError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ContentError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content, ContentError> {
+        self.build()
+    }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = Content;
+    type Error = ContentError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ContentError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content, ContentError> {
+        self.build()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Ok = Content;
+    type Error = ContentError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ContentError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content, ContentError> {
+        self.build()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+    type Ok = Content;
+    type Error = ContentError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ContentError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content, ContentError> {
+        self.build()
+    }
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = Content;
+    type Error = ContentError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ContentError> {
+        self.key = Some(Content::new(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ContentError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ContentError("map value serialized before its key".into()))?;
+        self.entries.push((key, Content::new(value)?));
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<Content, ContentError> {
+        self.entries.sort_by(|(a, _), (b, _)| compare_keys(a, b));
+        Ok(Content::Map(self.entries))
+    }
+}
+
+impl ser::SerializeStruct for StructBuilder {
+    type Ok = Content;
+    type Error = ContentError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ContentError> {
+        self.fields.push((key, Content::new(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Content, ContentError> {
+        self.build()
+    }
+}
+
+impl ser::SerializeStructVariant for StructBuilder {
+    type Ok = Content;
+    type Error = ContentError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ContentError> {
+        self.fields.push((key, Content::new(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Content, ContentError> {
+        self.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Content;
+
+    #[test]
+    fn sorted_maps() {
+        let mut map = HashMap::new();
+        for key in [10u32, 2, 33, 1] {
+            map.insert(key, ());
+        }
+        let keys: Vec<_> = match Content::new(&map).unwrap() {
+            Content::Map(entries) => entries.into_iter().map(|(key, _)| key).collect(),
+            other => panic!("Expected a map, got {:?}", other),
+        };
+        assert_eq!(keys, [Content::U64(1), Content::U64(2), Content::U64(10), Content::U64(33)]);
+    }
+}
@@ -0,0 +1,104 @@
+//! Histogram diff, as in git: the longest common region containing the rarest token on the old
+//! side is matched up, and the regions before and after it are diffed recursively. Regions where
+//! every token is too common, or which are nested too deeply, fall back to Myers' algorithm.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+
+use super::{common_prefix_len, common_suffix_len, myers, Ops, ChangeTag};
+
+/// Tokens which occur more often than this on the old side are not used to match regions.
+const MAX_CHAIN_LEN: usize = 64;
+
+/// Regions nested deeper than this are diffed with Myers' algorithm. Each level of recursion
+/// scans all of its regions, so without a bound a run of small changes, which only splits off a
+/// few tokens at each level, would take quadratic time.
+const MAX_DEPTH: usize = 32;
+
+pub(crate) fn diff<T: Hash + Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    ops: &mut Ops,
+) {
+    diff_region(old, old_range, new, new_range, 0, ops);
+}
+
+fn diff_region<T: Hash + Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    depth: usize,
+    ops: &mut Ops,
+) {
+    let prefix = common_prefix_len(&old[old_range.clone()], &new[new_range.clone()]);
+    let (old_start, new_start) = (old_range.start + prefix, new_range.start + prefix);
+    ops.push(ChangeTag::Equal, old_range.start..old_start, new_range.start..new_start);
+    let suffix = common_suffix_len(&old[old_start..old_range.end], &new[new_start..new_range.end]);
+    let (old_end, new_end) = (old_range.end - suffix, new_range.end - suffix);
+
+    let region = if old_start == old_end || new_start == new_end || depth >= MAX_DEPTH {
+        None
+    } else {
+        find_region(old, old_start..old_end, new, new_start..new_end)
+    };
+    if old_start == old_end || new_start == new_end {
+        ops.push(ChangeTag::Delete, old_start..old_end, new_start..new_start);
+        ops.push(ChangeTag::Insert, old_end..old_end, new_start..new_end);
+    } else if let Some((i, j, len)) = region {
+        diff_region(old, old_start..i, new, new_start..j, depth + 1, ops);
+        ops.push(ChangeTag::Equal, i..i + len, j..j + len);
+        diff_region(old, i + len..old_end, new, j + len..new_end, depth + 1, ops);
+    } else {
+        myers::diff(old, old_start..old_end, new, new_start..new_end, ops);
+    }
+
+    ops.push(ChangeTag::Equal, old_end..old_range.end, new_end..new_range.end);
+}
+
+/// Finds the common region, as its start on each side and its length, whose rarest token occurs
+/// least often on the old side, preferring longer regions among equally rare ones.
+fn find_region<T: Hash + Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+) -> Option<(usize, usize, usize)> {
+    let mut positions: HashMap<&T, Vec<usize>> = HashMap::new();
+    for i in old_range.clone() {
+        positions.entry(&old[i]).or_default().push(i);
+    }
+
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    let mut j = new_range.start;
+    while j < new_range.end {
+        let mut next = j + 1;
+        let occurrences = match positions.get(&new[j]) {
+            Some(occurrences) if occurrences.len() <= MAX_CHAIN_LEN => occurrences,
+            _ => {
+                j = next;
+                continue;
+            }
+        };
+        for &i in occurrences {
+            let before = common_suffix_len(&old[old_range.start..i], &new[new_range.start..j]);
+            let after = common_prefix_len(&old[i..old_range.end], &new[j..new_range.end]);
+            let (start_i, start_j, len) = (i - before, j - before, before + after);
+            let rarity = (start_i..start_i + len)
+                .map(|k| positions[&old[k]].len())
+                .min()
+                .unwrap_or(usize::MAX);
+            if best.is_none_or(|(_, _, best_len, best_rarity)| {
+                rarity < best_rarity || (rarity == best_rarity && len > best_len)
+            }) {
+                best = Some((start_i, start_j, len, rarity));
+            }
+            next = next.max(start_j + len);
+        }
+        j = next;
+    }
+    best.map(|(i, j, len, _)| (i, j, len))
+}
@@ -0,0 +1,482 @@
+//! Diffing for rendering mismatched snapshots. Snapshots are compared for equality first, so
+//! diffs are only computed for snapshots which are known to differ.
+
+use std::fmt;
+use std::hash::Hash;
+use std::io::IsTerminal;
+use std::ops::Range;
+use std::sync::Once;
+
+mod histogram;
+mod myers;
+mod patience;
+mod render;
+
+/// The environment variable used to pick the diff algorithm and granularity, as a comma separated
+/// list such as `histogram,context=5,color=never,highlight=char`.
+pub const DIFF_VAR: &str = "SNAPSHOT_DIFF";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Myers' algorithm, which finds a minimal diff.
+    #[default]
+    Myers,
+    /// Patience diff, which matches up unique lines first. This keeps moved or reordered blocks
+    /// readable.
+    Patience,
+    /// Histogram diff, which extends patience diff to lines which are rare but not unique.
+    Histogram,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Granularity {
+    #[default]
+    Line,
+    /// Words, runs of whitespace and single punctuation characters.
+    Word,
+    Char,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Color {
+    /// Use colors unless stderr is not a terminal or `NO_COLOR` is set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            Color::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && std::io::stderr().is_terminal()
+            }
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+/// How the changes inside a changed line are emphasized, when lines are replaced by similar ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Highlight {
+    None,
+    #[default]
+    Word,
+    Char,
+}
+
+/// How differences between snapshots are rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiffOptions {
+    pub algorithm: Algorithm,
+    pub granularity: Granularity,
+    /// The number of unchanged lines shown around each change in a line diff.
+    pub context: usize,
+    pub color: Color,
+    /// Emphasizes the changed words or characters of changed lines in a colored line diff.
+    pub highlight: Highlight,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            algorithm: Algorithm::default(),
+            granularity: Granularity::default(),
+            context: 3,
+            color: Color::default(),
+            highlight: Highlight::default(),
+        }
+    }
+}
+
+impl DiffOptions {
+    /// Reads the options from `SNAPSHOT_DIFF`, using the defaults for anything it doesn't set.
+    pub fn from_env() -> Self {
+        DiffOptions::default().with_env()
+    }
+
+    /// Overrides these options with those in `SNAPSHOT_DIFF`, if it is set. Unknown options are
+    /// only warned about once per process, rather than on every assertion.
+    pub(crate) fn with_env(self) -> Self {
+        static WARNED: Once = Once::new();
+        let value = match std::env::var(DIFF_VAR) {
+            Ok(value) => value,
+            Err(_) => return self,
+        };
+        let mut unknown = Vec::new();
+        let options = self.parse_with(&value, |word| unknown.push(word.to_owned()));
+        if !unknown.is_empty() {
+            WARNED.call_once(|| {
+                for word in unknown {
+                    eprintln!("Ignoring unknown {} option `{}`", DIFF_VAR, word);
+                }
+            });
+        }
+        options
+    }
+
+    /// Overrides these options with those in `value`, in the format of `SNAPSHOT_DIFF`.
+    pub(crate) fn parse(self, value: &str) -> Self {
+        self.parse_with(value, |word| eprintln!("Ignoring unknown {} option `{}`", DIFF_VAR, word))
+    }
+
+    /// Like `parse`, but calls `unknown` with each unknown option.
+    fn parse_with(self, value: &str, mut unknown: impl FnMut(&str)) -> Self {
+        let mut options = self;
+        for word in value.split(',').map(str::trim).filter(|word| !word.is_empty()) {
+            match word {
+                "myers" => options.algorithm = Algorithm::Myers,
+                "patience" => options.algorithm = Algorithm::Patience,
+                "histogram" => options.algorithm = Algorithm::Histogram,
+                "line" => options.granularity = Granularity::Line,
+                "word" => options.granularity = Granularity::Word,
+                "char" => options.granularity = Granularity::Char,
+                "color=auto" => options.color = Color::Auto,
+                "color=always" => options.color = Color::Always,
+                "color=never" => options.color = Color::Never,
+                "highlight=none" => options.highlight = Highlight::None,
+                "highlight=word" => options.highlight = Highlight::Word,
+                "highlight=char" => options.highlight = Highlight::Char,
+                _ => match word.strip_prefix("context=").and_then(|context| context.parse().ok()) {
+                    Some(context) => options.context = context,
+                    None => unknown(word),
+                },
+            }
+        }
+        options
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A run of equal, deleted or inserted tokens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Op {
+    pub tag: ChangeTag,
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// A list of ops in order, with adjacent ops of the same kind merged and deletions kept before
+/// insertions.
+#[derive(Debug, Default)]
+pub(crate) struct Ops(Vec<Op>);
+
+impl Ops {
+    pub fn push(&mut self, tag: ChangeTag, old: Range<usize>, new: Range<usize>) {
+        if old.is_empty() && new.is_empty() {
+            return;
+        }
+        match self.0.last_mut() {
+            Some(last) if last.tag == tag => {
+                last.old.end = old.end;
+                last.new.end = new.end;
+            }
+            Some(last) if last.tag == ChangeTag::Insert && tag == ChangeTag::Delete => {
+                let insert = self.0.pop().unwrap();
+                self.push(ChangeTag::Delete, old.clone(), insert.new.start..insert.new.start);
+                self.push(ChangeTag::Insert, old.end..old.end, insert.new);
+            }
+            _ => self.0.push(Op { tag, old, new }),
+        }
+    }
+}
+
+/// Splits `text` into tokens of the given granularity. Lines keep their line endings so that a
+/// missing final newline shows up as a difference.
+fn tokenize(text: &str, granularity: Granularity) -> Vec<&str> {
+    match granularity {
+        Granularity::Line => text.split_inclusive('\n').collect(),
+        Granularity::Word => {
+            let mut words = Vec::new();
+            let mut rest = text;
+            while let Some(c) = rest.chars().next() {
+                let class = char_class(c);
+                let len = if class == CharClass::Other {
+                    c.len_utf8()
+                } else {
+                    rest.find(|c| char_class(c) != class).unwrap_or(rest.len())
+                };
+                words.push(&rest[..len]);
+                rest = &rest[len..];
+            }
+            words
+        }
+        Granularity::Char => text.char_indices().map(|(i, c)| &text[i..i + c.len_utf8()]).collect(),
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Space,
+    Newline,
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c == '\n' {
+        CharClass::Newline
+    } else if c.is_whitespace() {
+        CharClass::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Other
+    }
+}
+
+pub(crate) fn diff_tokens<T: Hash + Eq>(old: &[T], new: &[T], algorithm: Algorithm) -> Vec<Op> {
+    let mut ops = Ops::default();
+    let (old_range, new_range) = (0..old.len(), 0..new.len());
+    match algorithm {
+        Algorithm::Myers => myers::diff(old, old_range, new, new_range, &mut ops),
+        Algorithm::Patience => patience::diff(old, old_range, new, new_range, &mut ops),
+        Algorithm::Histogram => histogram::diff(old, old_range, new, new_range, &mut ops),
+    }
+    ops.0
+}
+
+/// A line diff between the expected and actual value of a snapshot. Its `Display` implementation
+/// renders it with the options it was created with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diff {
+    pub expected: String,
+    pub actual: String,
+    /// The changed lines, with up to `DiffOptions::context` unchanged lines around them.
+    pub hunks: Vec<Hunk>,
+    /// The number of lines only in the actual value.
+    pub insertions: usize,
+    /// The number of lines only in the expected value.
+    pub deletions: usize,
+    options: DiffOptions,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    /// The 0-based range of expected lines covered by the hunk.
+    pub old: Range<usize>,
+    /// The 0-based range of actual lines covered by the hunk.
+    pub new: Range<usize>,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffLine {
+    pub tag: ChangeTag,
+    /// The 1-based line number in the expected value, unless the line was inserted.
+    pub old_line: Option<usize>,
+    /// The 1-based line number in the actual value, unless the line was deleted.
+    pub new_line: Option<usize>,
+    /// The text of the line, including its line ending if it has one.
+    pub text: String,
+}
+
+impl Diff {
+    pub fn new(expected: &str, actual: &str, options: &DiffOptions) -> Self {
+        let old = tokenize(expected, Granularity::Line);
+        let new = tokenize(actual, Granularity::Line);
+        let ops = diff_tokens(&old, &new, options.algorithm);
+        let count = |tag| ops.iter().filter(|op| op.tag == tag).map(|op| op.old.len() + op.new.len()).sum();
+        Diff {
+            expected: expected.to_owned(),
+            actual: actual.to_owned(),
+            hunks: render::hunks(&old, &new, &ops, options.context),
+            insertions: count(ChangeTag::Insert),
+            deletions: count(ChangeTag::Delete),
+            options: *options,
+        }
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let style = render::Style::new(self.options.color.enabled());
+        if self.options.granularity == Granularity::Line {
+            f.write_str(&render::unified(&self.hunks, self.options.highlight, &style))
+        } else {
+            let old = tokenize(&self.expected, self.options.granularity);
+            let new = tokenize(&self.actual, self.options.granularity);
+            let ops = diff_tokens(&old, &new, self.options.algorithm);
+            f.write_str(&render::inline(&old, &new, &ops, &style))
+        }
+    }
+}
+
+/// Renders a diff from `expected` to `actual`, with the options from `SNAPSHOT_DIFF`.
+pub fn render_diff(expected: &str, actual: &str) -> String {
+    render_diff_with(expected, actual, &DiffOptions::from_env())
+}
+
+/// Renders a diff from `expected` to `actual`. Line diffs are split into hunks with line numbers,
+/// while word and char diffs are rendered inline.
+pub fn render_diff_with(expected: &str, actual: &str, options: &DiffOptions) -> String {
+    Diff::new(expected, actual, options).to_string()
+}
+
+fn common_prefix_len<T: PartialEq>(old: &[T], new: &[T]) -> usize {
+    old.iter().zip(new).take_while(|(old, new)| old == new).count()
+}
+
+fn common_suffix_len<T: PartialEq>(old: &[T], new: &[T]) -> usize {
+    old.iter().rev().zip(new.iter().rev()).take_while(|(old, new)| old == new).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        diff_tokens, render_diff_with, tokenize, Algorithm, Color, DiffOptions, Granularity, Highlight, Op, ChangeTag,
+    };
+
+    const ALGORITHMS: [Algorithm; 3] = [Algorithm::Myers, Algorithm::Patience, Algorithm::Histogram];
+
+    /// Applies `ops` to `old`, checking that they reproduce `new`, and returns the edit distance.
+    fn check_ops<T: PartialEq + Clone + std::fmt::Debug>(old: &[T], new: &[T], ops: &[Op]) -> usize {
+        let mut result = Vec::new();
+        let (mut old_pos, mut new_pos) = (0, 0);
+        let mut distance = 0;
+        for op in ops {
+            assert_eq!((op.old.start, op.new.start), (old_pos, new_pos));
+            match op.tag {
+                ChangeTag::Equal => {
+                    assert_eq!(&old[op.old.clone()], &new[op.new.clone()]);
+                    result.extend_from_slice(&old[op.old.clone()]);
+                }
+                ChangeTag::Delete => distance += op.old.len(),
+                ChangeTag::Insert => {
+                    result.extend_from_slice(&new[op.new.clone()]);
+                    distance += op.new.len();
+                }
+            }
+            old_pos = op.old.end;
+            new_pos = op.new.end;
+        }
+        assert_eq!((old_pos, new_pos), (old.len(), new.len()));
+        assert_eq!(result, new);
+        distance
+    }
+
+    #[test]
+    fn algorithms() {
+        for (old, new, distance) in [
+            ("ABCABBA", "CBABAC", 5),
+            ("", "abc", 3),
+            ("abc", "", 3),
+            ("abc", "abc", 0),
+            ("the quick brown fox", "the quack brown box", 4),
+            ("aaaaaaaaaa", "aaaaabaaaaa", 1),
+        ] {
+            let (old, new): (Vec<char>, Vec<char>) = (old.chars().collect(), new.chars().collect());
+            assert_eq!(check_ops(&old, &new, &diff_tokens(&old, &new, Algorithm::Myers)), distance);
+            for algorithm in ALGORITHMS {
+                assert!(check_ops(&old, &new, &diff_tokens(&old, &new, algorithm)) >= distance);
+            }
+        }
+    }
+
+    #[test]
+    fn moved_block() {
+        let old = tokenize("fn a() {\n    one();\n}\n\nfn b() {\n    two();\n}\n", Granularity::Line);
+        let new = tokenize("fn b() {\n    two();\n}\n\nfn a() {\n    one();\n}\n", Granularity::Line);
+        for algorithm in [Algorithm::Patience, Algorithm::Histogram] {
+            let ops = diff_tokens(&old, &new, algorithm);
+            assert_eq!(check_ops(&old, &new, &ops), 8);
+            // One function is kept, rather than the lines which occur in both functions.
+            let kept: Vec<&str> = ops
+                .iter()
+                .filter(|op| op.tag == ChangeTag::Equal)
+                .flat_map(|op| old[op.old.clone()].iter().copied())
+                .collect();
+            assert!(kept.contains(&"    one();\n") || kept.contains(&"    two();\n"), "{:?}", ops);
+        }
+    }
+
+    #[test]
+    fn large_inputs() {
+        let old: Vec<String> = (0..10_000).map(|i| format!("old {}\n", i)).collect();
+        let disjoint: Vec<String> = (0..10_000).map(|i| format!("new {}\n", i)).collect();
+        let mut alternating = old.clone();
+        for line in alternating.iter_mut().step_by(2) {
+            *line = line.to_uppercase();
+        }
+        for algorithm in ALGORITHMS {
+            let start = std::time::Instant::now();
+            let ops = diff_tokens(&old, &disjoint, algorithm);
+            assert_eq!(check_ops(&old, &disjoint, &ops), 20_000);
+
+            // Past the cost limits, the diff is still correct, though no longer minimal.
+            let ops = diff_tokens(&old, &alternating, algorithm);
+            let distance = check_ops(&old, &alternating, &ops);
+            assert!((10_000..15_000).contains(&distance), "{:?}: {}", algorithm, distance);
+            let elapsed = start.elapsed();
+            assert!(elapsed < std::time::Duration::from_secs(10), "{:?} took {:?}", algorithm, elapsed);
+        }
+    }
+
+    #[test]
+    fn one_anchor_per_level() {
+        // Only the last line of each gap is unique on both sides, so each level of patience's
+        // recursion finds a single anchor.
+        let mut old = vec!["a0".to_owned()];
+        for i in 1..5_000 {
+            old.push(format!("a{}", i));
+            old.push(format!("a{}", i - 1));
+        }
+        let mut new = vec!["b".to_owned()];
+        new.extend((1..5_000).map(|i| format!("a{}", i)));
+        for algorithm in ALGORITHMS {
+            let start = std::time::Instant::now();
+            check_ops(&old, &new, &diff_tokens(&old, &new, algorithm));
+            let elapsed = start.elapsed();
+            assert!(elapsed < std::time::Duration::from_secs(10), "{:?} took {:?}", algorithm, elapsed);
+        }
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(DiffOptions::default().parse(""), DiffOptions::default());
+        assert_eq!(
+            DiffOptions::default().parse("histogram, word,context=1,color=never"),
+            DiffOptions {
+                algorithm: Algorithm::Histogram,
+                granularity: Granularity::Word,
+                context: 1,
+                color: Color::Never,
+                highlight: Highlight::Word,
+            }
+        );
+        assert_eq!(tokenize("a_b, c\n\n", Granularity::Word), ["a_b", ",", " ", "c", "\n\n"]);
+    }
+
+    #[test]
+    fn render() {
+        let lines = DiffOptions {
+            color: Color::Always,
+            ..DiffOptions::default()
+        };
+        assert_eq!(
+            render_diff_with("a\nb\nc\n", "a\nc\nd", &lines),
+            "\x1b[36m@@ -1,3 +1,3 @@\x1b[0m\n\
+             \x1b[2m1 1 |\x1b[0m a\n\
+             \x1b[2m2   |\x1b[0m\x1b[91m-b\x1b[0m\n\
+             \x1b[2m3 2 |\x1b[0m c\n\
+             \x1b[2m  3 |\x1b[0m\x1b[92m+d\x1b[0m\n\
+             \\ No newline at end of file\n"
+        );
+        let words = DiffOptions {
+            granularity: Granularity::Word,
+            ..lines
+        };
+        assert_eq!(
+            render_diff_with("hello world\n", "hello there\n", &words),
+            "hello \x1b[91mworld\x1b[0m\x1b[92mthere\x1b[0m\n"
+        );
+    }
+}
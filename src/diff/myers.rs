@@ -0,0 +1,159 @@
+//! Myers' O((N+M)D) diff algorithm, using the linear space refinement from "An O(ND) Difference
+//! Algorithm and Its Variations" (Myers, 1986): the middle snake of the edit graph is found by
+//! searching forwards and backwards at once, and the halves on either side are diffed
+//! recursively.
+//!
+//! As in git's xdiff, the search for a middle snake gives up after an edit cost of about the
+//! square root of the input length (but at least `MIN_COST_LIMIT`), and splits at the furthest
+//! point the forward search reached instead. The diff is then no longer minimal, but very
+//! different inputs take O(N+M) steps of bounded cost rather than O((N+M)D) time.
+
+use std::ops::{Index, IndexMut, Range};
+
+use super::{common_prefix_len, common_suffix_len, Ops, ChangeTag};
+
+pub(crate) fn diff<T: PartialEq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    ops: &mut Ops,
+) {
+    let max_d = max_d(old_range.len(), new_range.len());
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    conquer(old, old_range, new, new_range, &mut vf, &mut vb, ops);
+}
+
+/// The furthest reaching x coordinate on each diagonal k = x - y, indexed by k.
+struct V {
+    offset: isize,
+    v: Vec<usize>,
+}
+
+impl V {
+    fn new(max_d: usize) -> Self {
+        V {
+            offset: max_d as isize,
+            v: vec![0; 2 * max_d],
+        }
+    }
+}
+
+impl Index<isize> for V {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.v[(k + self.offset) as usize]
+    }
+}
+
+impl IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.v[(k + self.offset) as usize]
+    }
+}
+
+/// The lowest edit cost after which the search for a middle snake gives up.
+const MIN_COST_LIMIT: usize = 256;
+
+fn max_d(old_len: usize, new_len: usize) -> usize {
+    (old_len + new_len).div_ceil(2) + 1
+}
+
+fn cost_limit(old_len: usize, new_len: usize) -> usize {
+    (((old_len + new_len) as f64).sqrt() as usize).max(MIN_COST_LIMIT)
+}
+
+/// Finds the start of the middle snake of the edit graph of `old` and `new`, which must not share
+/// a common prefix or suffix. If that costs too much, returns the furthest point reached by the
+/// forward search instead, which lies on some (not necessarily shortest) edit path.
+fn find_middle_snake<T: PartialEq>(old: &[T], new: &[T], vf: &mut V, vb: &mut V) -> Option<(usize, usize)> {
+    let (n, m) = (old.len(), new.len());
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    vf[1] = 0;
+    vb[1] = 0;
+
+    let max_d = max_d(n, m).min(cost_limit(n, m) + 1) as isize;
+    for d in 0..max_d {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
+                vf[k + 1]
+            } else {
+                vf[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+            if x < n && y < m {
+                x += common_prefix_len(&old[x..], &new[y..]);
+            }
+            vf[k] = x;
+            if odd && (k - delta).abs() < d && vf[k] + vb[-(k - delta)] >= n {
+                return Some((x0, y0));
+            }
+        }
+
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vb[k - 1] < vb[k + 1]) {
+                vb[k + 1]
+            } else {
+                vb[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if x < n && y < m {
+                let advance = common_suffix_len(&old[..n - x], &new[..m - y]);
+                x += advance;
+                y += advance;
+            }
+            vb[k] = x;
+            if !odd && (k - delta).abs() <= d && vb[k] + vf[-(k - delta)] >= n {
+                return Some((n - x, m - y));
+            }
+        }
+    }
+
+    let d = max_d - 1;
+    (-d..=d)
+        .step_by(2)
+        .map(|k| (vf[k], vf[k] as isize - k))
+        .filter(|&(x, y)| x <= n && y >= 0 && y as usize <= m)
+        .map(|(x, y)| (x, y as usize))
+        .filter(|&(x, y)| (x, y) != (0, 0) && (x, y) != (n, m))
+        .max_by_key(|&(x, y)| x + y)
+}
+
+fn conquer<T: PartialEq>(
+    old: &[T],
+    mut old_range: Range<usize>,
+    new: &[T],
+    mut new_range: Range<usize>,
+    vf: &mut V,
+    vb: &mut V,
+    ops: &mut Ops,
+) {
+    let prefix = common_prefix_len(&old[old_range.clone()], &new[new_range.clone()]);
+    ops.push(ChangeTag::Equal, old_range.start..old_range.start + prefix, new_range.start..new_range.start + prefix);
+    old_range.start += prefix;
+    new_range.start += prefix;
+
+    let suffix = common_suffix_len(&old[old_range.clone()], &new[new_range.clone()]);
+    let suffix_old = old_range.end - suffix..old_range.end;
+    let suffix_new = new_range.end - suffix..new_range.end;
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    if old_range.is_empty() || new_range.is_empty() {
+        ops.push(ChangeTag::Delete, old_range.clone(), new_range.start..new_range.start);
+        ops.push(ChangeTag::Insert, old_range.end..old_range.end, new_range);
+    } else if let Some((x, y)) = find_middle_snake(&old[old_range.clone()], &new[new_range.clone()], vf, vb) {
+        let (x, y) = (old_range.start + x, new_range.start + y);
+        conquer(old, old_range.start..x, new, new_range.start..y, vf, vb, ops);
+        conquer(old, x..old_range.end, new, y..new_range.end, vf, vb, ops);
+    } else {
+        ops.push(ChangeTag::Delete, old_range.clone(), new_range.start..new_range.start);
+        ops.push(ChangeTag::Insert, old_range.end..old_range.end, new_range);
+    }
+
+    ops.push(ChangeTag::Equal, suffix_old, suffix_new);
+}
@@ -0,0 +1,116 @@
+//! Patience diff: lines which occur exactly once on both sides are matched up using the longest
+//! increasing subsequence of their positions, and the gaps between them are diffed recursively.
+//! Regions without any unique lines, or which are nested too deeply, fall back to Myers' algorithm.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+
+use super::{common_prefix_len, common_suffix_len, myers, Ops, ChangeTag};
+
+/// Gaps nested deeper than this are diffed with Myers' algorithm. Each level of recursion scans
+/// all of its gaps, so without a bound inputs which only yield one anchor per level would take
+/// quadratic time, and overflow the stack.
+const MAX_DEPTH: usize = 32;
+
+pub(crate) fn diff<T: Hash + Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    ops: &mut Ops,
+) {
+    diff_gap(old, old_range, new, new_range, 0, ops);
+}
+
+fn diff_gap<T: Hash + Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    depth: usize,
+    ops: &mut Ops,
+) {
+    let prefix = common_prefix_len(&old[old_range.clone()], &new[new_range.clone()]);
+    let (old_start, new_start) = (old_range.start + prefix, new_range.start + prefix);
+    ops.push(ChangeTag::Equal, old_range.start..old_start, new_range.start..new_start);
+    let suffix = common_suffix_len(&old[old_start..old_range.end], &new[new_start..new_range.end]);
+    let (old_end, new_end) = (old_range.end - suffix, new_range.end - suffix);
+
+    let anchors = if depth < MAX_DEPTH {
+        unique_anchors(old, old_start..old_end, new, new_start..new_end)
+    } else {
+        Vec::new()
+    };
+    if anchors.is_empty() {
+        myers::diff(old, old_start..old_end, new, new_start..new_end, ops);
+    } else {
+        let (mut old_pos, mut new_pos) = (old_start, new_start);
+        for (old_anchor, new_anchor) in anchors {
+            diff_gap(old, old_pos..old_anchor, new, new_pos..new_anchor, depth + 1, ops);
+            ops.push(ChangeTag::Equal, old_anchor..old_anchor + 1, new_anchor..new_anchor + 1);
+            old_pos = old_anchor + 1;
+            new_pos = new_anchor + 1;
+        }
+        diff_gap(old, old_pos..old_end, new, new_pos..new_end, depth + 1, ops);
+    }
+
+    ops.push(ChangeTag::Equal, old_end..old_range.end, new_end..new_range.end);
+}
+
+/// Returns the longest sequence of tokens which are unique on both sides and occur in the same
+/// order on both sides, as pairs of positions.
+fn unique_anchors<T: Hash + Eq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+) -> Vec<(usize, usize)> {
+    // For each token: the number of occurrences and the last position on each side.
+    let mut counts: HashMap<&T, (usize, usize, usize, usize)> = HashMap::new();
+    for i in old_range {
+        let entry = counts.entry(&old[i]).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for j in new_range {
+        if let Some(entry) = counts.get_mut(&new[j]) {
+            entry.2 += 1;
+            entry.3 = j;
+        }
+    }
+    let mut unique: Vec<(usize, usize)> = counts
+        .into_values()
+        .filter(|&(old_count, _, new_count, _)| old_count == 1 && new_count == 1)
+        .map(|(_, i, _, j)| (i, j))
+        .collect();
+    unique.sort_unstable_by_key(|&(_, j)| j);
+    longest_increasing_subsequence(&unique)
+}
+
+/// Finds the longest subsequence of `pairs`, which are sorted by their second element, whose
+/// first elements are also increasing, using patience sorting.
+fn longest_increasing_subsequence(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // The index of the top card of each pile, and the top card of the previous pile at the time
+    // each card was placed.
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = Vec::with_capacity(pairs.len());
+    for (index, &(i, _)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|&top| pairs[top].0 < i);
+        previous.push(pile.checked_sub(1).map(|pile| piles[pile]));
+        if pile == piles.len() {
+            piles.push(index);
+        } else {
+            piles[pile] = index;
+        }
+    }
+
+    let mut sequence = Vec::with_capacity(piles.len());
+    let mut card = piles.last().copied();
+    while let Some(index) = card {
+        sequence.push(pairs[index]);
+        card = previous[index];
+    }
+    sequence.reverse();
+    sequence
+}
@@ -0,0 +1,347 @@
+use std::fmt::Write;
+
+use super::{diff_tokens, tokenize, Algorithm, ChangeTag, DiffLine, Granularity, Highlight, Hunk, Op};
+
+/// ANSI escape codes, which are all empty when colors are disabled.
+pub(super) struct Style {
+    color: bool,
+    delete: &'static str,
+    insert: &'static str,
+    header: &'static str,
+    line_number: &'static str,
+    emphasis: &'static str,
+    end_emphasis: &'static str,
+    reset: &'static str,
+}
+
+impl Style {
+    pub fn new(color: bool) -> Self {
+        if color {
+            Style {
+                color,
+                delete: "\x1b[91m",
+                insert: "\x1b[92m",
+                header: "\x1b[36m",
+                line_number: "\x1b[2m",
+                emphasis: "\x1b[7m",
+                end_emphasis: "\x1b[27m",
+                reset: "\x1b[0m",
+            }
+        } else {
+            Style {
+                color,
+                delete: "",
+                insert: "",
+                header: "",
+                line_number: "",
+                emphasis: "",
+                end_emphasis: "",
+                reset: "",
+            }
+        }
+    }
+}
+
+/// Splits line `ops` into hunks of changes, each surrounded by up to `context` equal lines.
+/// Changes separated by at most `2 * context` equal lines share a hunk.
+pub(super) fn hunks(old: &[&str], new: &[&str], ops: &[Op], context: usize) -> Vec<Hunk> {
+    let mut grouped = Vec::new();
+    let mut current = Vec::new();
+    for (index, op) in ops.iter().enumerate() {
+        if op.tag != ChangeTag::Equal {
+            current.push(op.clone());
+            continue;
+        }
+        let (first, last) = (index == 0, index == ops.len() - 1);
+        let len = op.old.len();
+        if !first && !last && len <= 2 * context {
+            current.push(op.clone());
+            continue;
+        }
+        let trailing = if first { 0 } else { context.min(len) };
+        let leading = if last { 0 } else { context.min(len) };
+        if trailing > 0 {
+            current.push(Op {
+                tag: ChangeTag::Equal,
+                old: op.old.start..op.old.start + trailing,
+                new: op.new.start..op.new.start + trailing,
+            });
+        }
+        if !current.is_empty() {
+            grouped.push(std::mem::take(&mut current));
+        }
+        if leading > 0 {
+            current.push(Op {
+                tag: ChangeTag::Equal,
+                old: op.old.end - leading..op.old.end,
+                new: op.new.end - leading..op.new.end,
+            });
+        }
+    }
+    if current.iter().any(|op| op.tag != ChangeTag::Equal) {
+        grouped.push(current);
+    }
+
+    grouped
+        .into_iter()
+        .map(|ops| {
+            let mut lines = Vec::new();
+            for op in &ops {
+                for offset in 0..op.old.len().max(op.new.len()) {
+                    let (i, j) = (op.old.start + offset, op.new.start + offset);
+                    lines.push(match op.tag {
+                        ChangeTag::Equal => line(op.tag, Some(i), Some(j), old[i]),
+                        ChangeTag::Delete => line(op.tag, Some(i), None, old[i]),
+                        ChangeTag::Insert => line(op.tag, None, Some(j), new[j]),
+                    });
+                }
+            }
+            Hunk {
+                old: ops[0].old.start..ops[ops.len() - 1].old.end,
+                new: ops[0].new.start..ops[ops.len() - 1].new.end,
+                lines,
+            }
+        })
+        .collect()
+}
+
+fn line(tag: ChangeTag, old_index: Option<usize>, new_index: Option<usize>, text: &str) -> DiffLine {
+    DiffLine {
+        tag,
+        old_line: old_index.map(|i| i + 1),
+        new_line: new_index.map(|j| j + 1),
+        text: text.to_owned(),
+    }
+}
+
+/// Renders `hunks` with `@@` headers, prefixing each line with its old and new line numbers.
+pub(super) fn unified(hunks: &[Hunk], highlight: Highlight, style: &Style) -> String {
+    let last_line = hunks.last().map_or(0, |hunk| hunk.old.end.max(hunk.new.end));
+    let width = last_line.to_string().len();
+    let mut rendered = String::new();
+    for hunk in hunks {
+        writeln!(
+            rendered,
+            "{}@@ -{} +{} @@{}",
+            style.header,
+            hunk_range(&hunk.old),
+            hunk_range(&hunk.new),
+            style.reset
+        )
+        .unwrap();
+        let emphasized = emphasize(&hunk.lines, highlight, style);
+        for (index, line) in hunk.lines.iter().enumerate() {
+            let (sign, color) = match line.tag {
+                ChangeTag::Equal => (' ', ""),
+                ChangeTag::Delete => ('-', style.delete),
+                ChangeTag::Insert => ('+', style.insert),
+            };
+            let number = |line: Option<usize>| line.map_or_else(String::new, |line| line.to_string());
+            let (text, newline) = match line.text.strip_suffix('\n') {
+                Some(text) => (text, true),
+                None => (line.text.as_str(), false),
+            };
+            let text = emphasized[index].as_deref().unwrap_or(text);
+            writeln!(
+                rendered,
+                "{}{:>width$} {:>width$} |{}{}{}{}{}",
+                style.line_number,
+                number(line.old_line),
+                number(line.new_line),
+                style.reset,
+                color,
+                sign,
+                text,
+                if color.is_empty() { "" } else { style.reset },
+                width = width
+            )
+            .unwrap();
+            if !newline {
+                rendered.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    rendered
+}
+
+/// Pairs up each run of deleted lines directly followed by a run of inserted lines, and renders
+/// each similar pair with its changed words or characters emphasized. Returns the rendered text of
+/// each line, without its line ending, if it was emphasized.
+fn emphasize(lines: &[DiffLine], highlight: Highlight, style: &Style) -> Vec<Option<String>> {
+    let mut emphasized = vec![None; lines.len()];
+    let granularity = match highlight {
+        Highlight::Word if style.color => Granularity::Word,
+        Highlight::Char if style.color => Granularity::Char,
+        _ => return emphasized,
+    };
+    let run = |from: usize, tag| from + lines[from..].iter().take_while(|line| line.tag == tag).count();
+    let mut start = 0;
+    while start < lines.len() {
+        let deletes_end = run(start, ChangeTag::Delete);
+        let inserts_end = run(deletes_end, ChangeTag::Insert);
+        if deletes_end == start || inserts_end == deletes_end {
+            start = inserts_end.max(start + 1);
+            continue;
+        }
+        for (i, j) in (start..deletes_end).zip(deletes_end..inserts_end) {
+            let old_line = lines[i].text.strip_suffix('\n').unwrap_or(&lines[i].text);
+            let new_line = lines[j].text.strip_suffix('\n').unwrap_or(&lines[j].text);
+            if let Some((old_text, new_text)) = emphasize_pair(old_line, new_line, granularity, style) {
+                emphasized[i] = Some(old_text);
+                emphasized[j] = Some(new_text);
+            }
+        }
+        start = inserts_end;
+    }
+    emphasized
+}
+
+fn emphasize_pair(old_line: &str, new_line: &str, granularity: Granularity, style: &Style) -> Option<(String, String)> {
+    let old_tokens = tokenize(old_line, granularity);
+    let new_tokens = tokenize(new_line, granularity);
+    let ops = diff_tokens(&old_tokens, &new_tokens, Algorithm::Myers);
+    let common: usize = ops
+        .iter()
+        .filter(|op| op.tag == ChangeTag::Equal)
+        .map(|op| old_tokens[op.old.clone()].concat().trim().len())
+        .sum();
+    // Emphasizing most of a line is just noise, so only similar lines are emphasized.
+    if common * 4 < old_line.len() + new_line.len() {
+        return None;
+    }
+    let (mut old_text, mut new_text) = (String::new(), String::new());
+    for op in &ops {
+        match op.tag {
+            ChangeTag::Equal => {
+                old_text.push_str(&old_tokens[op.old.clone()].concat());
+                new_text.push_str(&new_tokens[op.new.clone()].concat());
+            }
+            ChangeTag::Delete => {
+                let text = old_tokens[op.old.clone()].concat();
+                write!(old_text, "{}{}{}", style.emphasis, text, style.end_emphasis).unwrap();
+            }
+            ChangeTag::Insert => {
+                let text = new_tokens[op.new.clone()].concat();
+                write!(new_text, "{}{}{}", style.emphasis, text, style.end_emphasis).unwrap();
+            }
+        }
+    }
+    Some((old_text, new_text))
+}
+
+/// Formats the 0-based line range as in a unified diff header: 1-based, with the length omitted if
+/// it is 1, and starting at the preceding line if it is empty.
+fn hunk_range(range: &std::ops::Range<usize>) -> String {
+    match range.len() {
+        0 => format!("{},0", range.start),
+        1 => format!("{}", range.start + 1),
+        len => format!("{},{}", range.start + 1, len),
+    }
+}
+
+/// Renders a word or char diff inline. Without colors, removed text is marked as `[-text-]` and
+/// added text as `{+text+}`.
+pub(super) fn inline(old: &[&str], new: &[&str], ops: &[Op], style: &Style) -> String {
+    let mut rendered = String::new();
+    for op in ops {
+        let (start, end, text) = match op.tag {
+            ChangeTag::Equal => ("", "", old[op.old.clone()].concat()),
+            ChangeTag::Delete if style.color => (style.delete, style.reset, old[op.old.clone()].concat()),
+            ChangeTag::Insert if style.color => (style.insert, style.reset, new[op.new.clone()].concat()),
+            ChangeTag::Delete => ("[-", "-]", old[op.old.clone()].concat()),
+            ChangeTag::Insert => ("{+", "+}", new[op.new.clone()].concat()),
+        };
+        if start.is_empty() {
+            rendered.push_str(&text);
+        } else if style.color {
+            // Color each line separately, so that colors don't bleed into the next line.
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    rendered.push('\n');
+                }
+                if !line.is_empty() {
+                    write!(rendered, "{}{}{}", start, line, end).unwrap();
+                }
+            }
+        } else {
+            write!(rendered, "{}{}{}", start, text, end).unwrap();
+        }
+    }
+    if !rendered.ends_with('\n') {
+        rendered.push('\n');
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hunk_range, Style};
+    use crate::diff::{diff_tokens, tokenize, Algorithm, ChangeTag, Diff, DiffOptions, Granularity, Highlight};
+
+    #[test]
+    fn unified() {
+        let old: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let new = old.replacen("2\n", "two\n", 1).replace("\n17\n", "\n17\nseventeen\n");
+        let diff = |context| {
+            let options = DiffOptions {
+                context,
+                ..DiffOptions::default()
+            };
+            Diff::new(&old, &new, &options)
+        };
+
+        let hunks = diff(3).hunks;
+        assert_eq!(hunks.len(), 2);
+        assert!(hunks.iter().all(|hunk| hunk.lines[0].tag == ChangeTag::Equal));
+        assert_eq!((hunks[1].old.clone(), hunks[1].new.clone()), (14..20, 14..21));
+        assert_eq!(diff(8).hunks.len(), 1);
+
+        assert_eq!(
+            super::unified(&diff(1).hunks, Highlight::Word, &Style::new(false)),
+            "\
+@@ -1,3 +1,3 @@
+ 1  1 | 1
+ 2    |-2
+    2 |+two
+ 3  3 | 3
+@@ -17,2 +17,3 @@
+17 17 | 17
+   18 |+seventeen
+18 19 | 18
+"
+        );
+        assert_eq!(hunk_range(&(4..4)), "4,0");
+    }
+
+    #[test]
+    fn emphasize() {
+        let diff = Diff::new(
+            "let x = compute(a, b);\nfoo\n",
+            "let x = compute(a, c);\nbar\n",
+            &DiffOptions::default(),
+        );
+        let lines = &diff.hunks[0].lines;
+        let style = Style::new(true);
+        let emphasized = super::emphasize(lines, Highlight::Word, &style);
+        assert_eq!(
+            emphasized,
+            [
+                Some("let x = compute(a, \x1b[7mb\x1b[27m);".to_owned()),
+                // Dissimilar lines are not emphasized.
+                None,
+                Some("let x = compute(a, \x1b[7mc\x1b[27m);".to_owned()),
+                None,
+            ]
+        );
+        assert!(super::emphasize(lines, Highlight::None, &style).iter().all(Option::is_none));
+        assert!(super::emphasize(lines, Highlight::Word, &Style::new(false)).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn inline() {
+        let old = tokenize("one two\nthree\n", Granularity::Word);
+        let new = tokenize("one 2\nthree", Granularity::Word);
+        let ops = diff_tokens(&old, &new, Algorithm::Myers);
+        assert_eq!(super::inline(&old, &new, &ops, &Style::new(false)), "one [-two-]{+2+}\nthree[-\n-]\n");
+    }
+}
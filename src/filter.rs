@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+
+use regex::Regex;
+
+/// A list of regex filters which scrub volatile text, such as timestamps or temporary paths,
+/// from the actual value of a text snapshot before it is compared or written.
+///
+/// Filters are applied in order. Filters for every snapshot of a crate are set in the project
+/// configuration (see `CONFIG_FILE`), or with `Settings::filters`.
+#[derive(Clone, Debug, Default)]
+pub struct Filters {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Filters {
+    pub fn new() -> Self {
+        Filters::default()
+    }
+
+    /// Replaces every match of `pattern` with `replacement`, which may refer to capture groups
+    /// as `$1` or `$name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regex.
+    pub fn add(self, pattern: &str, replacement: impl Into<String>) -> Self {
+        self.try_add(pattern, replacement)
+            .unwrap_or_else(|err| panic!("Invalid filter regex `{}`: {}", pattern, err))
+    }
+
+    pub(crate) fn try_add(mut self, pattern: &str, replacement: impl Into<String>) -> Result<Self, regex::Error> {
+        self.rules.push((Regex::new(pattern)?, replacement.into()));
+        Ok(self)
+    }
+
+    /// These filters followed by `other`.
+    pub(crate) fn then(mut self, other: &Filters) -> Self {
+        self.rules.extend(other.rules.iter().cloned());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for (regex, replacement) in &self.rules {
+            if let Cow::Owned(replaced) = regex.replace_all(&text, replacement.as_str()) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::Filters;
+
+    #[test]
+    fn filters() {
+        let filters = Filters::new()
+            .add(r"\d{4}-\d{2}-\d{2}", "[date]")
+            .add(r"/tmp/(\w+)/", "[tmp]/$1/");
+        assert_eq!(
+            filters.apply("at 2021-05-01 in /tmp/abc123/x/y"),
+            "at [date] in [tmp]/abc123/x/y"
+        );
+        assert!(matches!(filters.apply("no match"), Cow::Borrowed("no match")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid filter regex `(`")]
+    fn invalid_filter() {
+        Filters::new().add("(", "");
+    }
+}
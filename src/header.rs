@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+
+/// The version of the snapshot file format, written to snapshot headers.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Metadata written to the front-matter header of a snapshot file, e.g.
+///
+/// ```text
+/// ---
+/// source: src/lib.rs
+/// line: 12
+/// expression: value
+/// format_version: 1
+/// crate_version: 0.3.0
+/// ---
+/// ```
+///
+/// The header is optional, and is ignored when comparing a snapshot.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub source: Option<String>,
+    pub line: Option<u32>,
+    pub expression: Option<String>,
+    pub kind: Option<String>,
+    pub format_version: Option<u32>,
+    pub crate_version: Option<String>,
+}
+
+impl Metadata {
+    pub(crate) fn debug() -> Self {
+        Metadata {
+            kind: Some("debug".into()),
+            ..Metadata::default()
+        }
+    }
+
+    /// Parses the header at the start of `contents`, if there is one, and returns it along with
+    /// the rest of the contents.
+    pub fn parse(contents: &str) -> (Option<Metadata>, &str) {
+        Metadata::try_parse(contents).map_or((None, contents), |(metadata, body)| (Some(metadata), body))
+    }
+
+    /// Only blocks with known keys and a `format_version` are headers, so that snapshots which
+    /// merely start with YAML-style front matter are compared in full.
+    ///
+    /// The header may have CRLF line endings, e.g. when it was checked out on Windows, as it is
+    /// parsed before the body's line endings are normalised.
+    fn try_parse(contents: &str) -> Option<(Metadata, &str)> {
+        let (rest, end_marker) = match contents.strip_prefix("---\n") {
+            Some(rest) => (rest, "\n---\n"),
+            None => (contents.strip_prefix("---\r\n")?, "\r\n---\r\n"),
+        };
+        let end = rest.find(end_marker)?;
+        let (header, body) = (&rest[..end], &rest[end + end_marker.len()..]);
+        let mut metadata = Metadata::default();
+        for line in header.lines() {
+            let (key, value) = line.split_once(": ")?;
+            let value = value.to_owned();
+            match key {
+                "source" => metadata.source = Some(value),
+                "line" => metadata.line = Some(value.parse().ok()?),
+                "expression" => metadata.expression = Some(value),
+                "kind" => metadata.kind = Some(value),
+                "format_version" => metadata.format_version = Some(value.parse().ok()?),
+                "crate_version" => metadata.crate_version = Some(value),
+                _ => return None,
+            }
+        }
+        metadata.format_version?;
+        Some((metadata, body))
+    }
+
+    fn render(&self, body: &str) -> String {
+        let mut contents = String::from("---\n");
+        let mut field = |key: &str, value: &dyn std::fmt::Display| {
+            // Values must stay on one line for the header to parse.
+            let value = value.to_string().replace('\n', " ");
+            contents.push_str(&format!("{}: {}\n", key, value));
+        };
+        if let Some(source) = &self.source {
+            field("source", source);
+        }
+        if let Some(line) = &self.line {
+            field("line", line);
+        }
+        if let Some(expression) = &self.expression {
+            field("expression", expression);
+        }
+        if let Some(kind) = &self.kind {
+            field("kind", kind);
+        }
+        field("format_version", &FORMAT_VERSION);
+        field("crate_version", &env!("CARGO_PKG_VERSION"));
+        contents.push_str("---\n");
+        contents.push_str(body);
+        contents
+    }
+}
+
+/// Returns the contents of a snapshot file for `actual`, with a header if there is `metadata`.
+/// Without metadata, an empty header is still written if `actual` itself starts with something
+/// which parses as a header, as that would otherwise be stripped when the snapshot is read.
+pub(crate) fn snapshot_contents<'a>(actual: &'a str, metadata: Option<&Metadata>) -> Cow<'a, str> {
+    match metadata {
+        Some(metadata) => Cow::Owned(metadata.render(actual)),
+        None if Metadata::try_parse(actual).is_some() => Cow::Owned(Metadata::default().render(actual)),
+        None => Cow::Borrowed(actual),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metadata, FORMAT_VERSION};
+
+    #[test]
+    fn header() {
+        let metadata = Metadata {
+            source: Some("src/lib.rs".into()),
+            line: Some(12),
+            expression: Some("format!(\"{}\",\n x)".into()),
+            ..Metadata::default()
+        };
+        let contents = metadata.render("---\nbody\n");
+        assert_eq!(
+            contents,
+            format!(
+                "---\nsource: src/lib.rs\nline: 12\nexpression: format!(\"{{}}\",  x)\n\
+                 format_version: {}\ncrate_version: {}\n---\n---\nbody\n",
+                FORMAT_VERSION,
+                env!("CARGO_PKG_VERSION"),
+            )
+        );
+        let (parsed, body) = Metadata::parse(&contents);
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.line, Some(12));
+        assert_eq!(parsed.format_version, Some(FORMAT_VERSION));
+        assert_eq!(body, "---\nbody\n");
+
+        assert_eq!(Metadata::parse("---\nnot a header\n---\n"), (None, "---\nnot a header\n---\n"));
+        assert_eq!(Metadata::parse("---\n---\nbody\n"), (None, "---\n---\nbody\n"));
+        let front_matter = "---\ntitle: x\n---\nbody\n";
+        assert_eq!(Metadata::parse(front_matter), (None, front_matter));
+        let unknown = format!("---\nformat_version: {}\ntitle: x\n---\nbody\n", FORMAT_VERSION);
+        assert_eq!(Metadata::parse(&unknown), (None, unknown.as_str()));
+        let unversioned = "---\nkind: debug\n---\nSome(1)\n";
+        assert_eq!(Metadata::parse(unversioned), (None, unversioned));
+        assert_eq!(Metadata::parse("plain\n"), (None, "plain\n"));
+        let crlf = format!("---\r\nline: 3\r\nformat_version: {}\r\n---\r\nbody\r\n", FORMAT_VERSION);
+        let (parsed, body) = Metadata::parse(&crlf);
+        assert_eq!(parsed.unwrap().line, Some(3));
+        assert_eq!(body, "body\r\n");
+    }
+}
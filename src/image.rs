@@ -0,0 +1,255 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+
+use crate::{check_contents, write_atomic, Contents, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageOptions {
+    /// The largest difference in any channel for which two pixels are still considered equal.
+    pub threshold: u8,
+    /// The largest fraction of pixels which may differ before the images are considered different.
+    pub max_diff_ratio: f64,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            threshold: 0,
+            max_diff_ratio: 0.0,
+        }
+    }
+}
+
+struct Image<'a> {
+    actual: &'a [u8],
+    options: &'a ImageOptions,
+    /// Where to write an image highlighting the differing pixels.
+    diff_path: PathBuf,
+}
+
+impl Contents for Image<'_> {
+    fn contents(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.actual)
+    }
+
+    fn compare(&self, expected: &[u8], snapshot: &Path, show_diff: bool) -> Result<(), Error> {
+        let difference = || Error::Difference {
+            path: snapshot.to_owned(),
+            diff: None,
+        };
+        let actual = Rgba::decode(self.actual)?;
+        let expected = match Rgba::decode(expected) {
+            Ok(expected) => expected,
+            Err(_) if expected.is_empty() => return Err(difference()),
+            Err(err) => return Err(err),
+        };
+        if (actual.width, actual.height) != (expected.width, expected.height) {
+            if show_diff {
+                eprintln!(
+                    "Expected a {}x{} image, got {}x{}",
+                    expected.width, expected.height, actual.width, actual.height
+                );
+            }
+            return Err(difference());
+        }
+
+        let threshold = self.options.threshold;
+        let differs: Vec<bool> = actual
+            .pixels
+            .chunks(4)
+            .zip(expected.pixels.chunks(4))
+            .map(|(a, e)| a.iter().zip(e).any(|(&a, &e)| a.abs_diff(e) > threshold))
+            .collect();
+        let differing = differs.iter().filter(|&&differs| differs).count();
+        let ratio = differing as f64 / differs.len().max(1) as f64;
+        if differing == 0 || ratio <= self.options.max_diff_ratio {
+            return self.written();
+        }
+
+        write_atomic(&self.diff_path, &highlight(&expected, &differs)?)?;
+        if show_diff {
+            eprintln!(
+                "{} of {} pixels differ ({:.2}%), see {}",
+                differing,
+                differs.len(),
+                ratio * 100.0,
+                self.diff_path.display()
+            );
+        }
+        Err(difference())
+    }
+
+    /// Removes the diff image, which is stale once the snapshot matches.
+    fn written(&self) -> Result<(), Error> {
+        if self.diff_path.exists() {
+            std::fs::remove_file(&self.diff_path).map_err(Error::write(&self.diff_path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the PNG-encoded image `actual` against `snapshot`, allowing small differences as
+/// configured by `options`. If the images differ, an image highlighting the differing pixels is
+/// written next to the snapshot, e.g. `name.diff.png` for `name.png`.
+pub fn check_image_snapshot(
+    actual: &[u8],
+    snapshot: impl AsRef<Path>,
+    options: &ImageOptions,
+) -> Result<(), Error> {
+    check_contents(&image(actual, snapshot.as_ref(), options), snapshot.as_ref(), true)
+}
+
+pub(crate) fn check_image_snapshot_no_diff(
+    actual: &[u8],
+    snapshot: &Path,
+    options: &ImageOptions,
+) -> Result<(), Error> {
+    check_contents(&image(actual, snapshot, options), snapshot, false)
+}
+
+fn image<'a>(actual: &'a [u8], snapshot: &Path, options: &'a ImageOptions) -> Image<'a> {
+    Image {
+        actual,
+        options,
+        diff_path: snapshot.with_extension("diff.png"),
+    }
+}
+
+/// A decoded image with 8-bit RGBA pixels.
+struct Rgba {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Rgba {
+    fn decode(png: &[u8]) -> Result<Rgba, Error> {
+        let mut decoder = Decoder::new(png);
+        decoder.set_transformations(Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|err| Error::Image(err.to_string()))?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(|err| Error::Image(err.to_string()))?;
+        buffer.truncate(info.buffer_size());
+        let pixels = match info.color_type {
+            ColorType::Rgba => buffer,
+            ColorType::Rgb => buffer.chunks(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            ColorType::GrayscaleAlpha => buffer.chunks(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+            ColorType::Indexed => return Err(Error::Image("unexpanded indexed color".into())),
+        };
+        Ok(Rgba {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|err| Error::Image(err.to_string()))?;
+        writer.write_image_data(&self.pixels).map_err(|err| Error::Image(err.to_string()))?;
+        writer.finish().map_err(|err| Error::Image(err.to_string()))?;
+        Ok(png)
+    }
+}
+
+/// Encodes a copy of `expected`, faded to gray, with the differing pixels highlighted in red.
+fn highlight(expected: &Rgba, differs: &[bool]) -> Result<Vec<u8>, Error> {
+    let pixels = expected
+        .pixels
+        .chunks(4)
+        .zip(differs)
+        .flat_map(|(pixel, &differs)| {
+            if differs {
+                [255, 0, 0, 255]
+            } else {
+                let luma = (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000;
+                let faded = (192 + luma / 4) as u8;
+                [faded, faded, faded, 255]
+            }
+        })
+        .collect();
+    Rgba {
+        width: expected.width,
+        height: expected.height,
+        pixels,
+    }
+    .encode()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{image, ImageOptions, Rgba};
+    use crate::{Contents, Error, Settings, UpdateMode};
+
+    fn gradient(noise: u8) -> Vec<u8> {
+        let mut pixels: Vec<u8> = (0..8 * 8).flat_map(|i| [i * 4, 255 - i * 4, 128, 255]).collect();
+        pixels[4 * 9] += noise;
+        pixels[4 * 10 + 1] -= noise;
+        Rgba {
+            width: 8,
+            height: 8,
+            pixels,
+        }
+        .encode()
+        .unwrap()
+    }
+
+    #[test]
+    fn tolerance() {
+        let diff_path = Path::new("target/image-test/gradient.diff.png");
+        std::fs::create_dir_all(diff_path.parent().unwrap()).unwrap();
+        let (expected, noisy) = (gradient(0), gradient(2));
+        let compare = |threshold, max_diff_ratio| {
+            let options = ImageOptions {
+                threshold,
+                max_diff_ratio,
+            };
+            let snapshot = Path::new("target/image-test/gradient.png");
+            image(&noisy, snapshot, &options).compare(&expected, snapshot, false)
+        };
+
+        match compare(1, 0.0) {
+            Err(Error::Difference { diff: None, .. }) => {}
+            other => panic!("Expected `Err(Difference)`, got `{:?}`", other),
+        }
+        let diff = Rgba::decode(&std::fs::read(diff_path).unwrap()).unwrap();
+        assert_eq!(&diff.pixels[4 * 9..4 * 11], &[255, 0, 0, 255, 255, 0, 0, 255]);
+        assert_ne!(&diff.pixels[..4], &[255, 0, 0, 255]);
+
+        compare(2, 0.0).unwrap();
+        assert!(!diff_path.exists());
+        compare(0, 2.0 / 64.0).unwrap();
+    }
+
+    #[test]
+    fn update_removes_diff() {
+        let snapshot = Path::new("target/image-test/update.png");
+        let diff_path = snapshot.with_extension("diff.png");
+        std::fs::create_dir_all(snapshot.parent().unwrap()).unwrap();
+        std::fs::write(snapshot, gradient(0)).unwrap();
+        let check = |mode| {
+            Settings::default()
+                .update_mode(mode)
+                .bind(|| super::check_image_snapshot_no_diff(&gradient(2), snapshot, &ImageOptions::default()))
+        };
+
+        assert!(matches!(check(UpdateMode::New), Err(Error::Difference { .. })));
+        assert!(diff_path.exists());
+        assert!(matches!(check(UpdateMode::Always), Err(Error::Updated { .. })));
+        assert!(!diff_path.exists());
+        check(UpdateMode::New).unwrap();
+    }
+
+    #[test]
+    fn image_snapshot() {
+        super::check_image_snapshot(&gradient(1), "snapshots/image.png", &ImageOptions::default()).unwrap();
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::settings::with_current;
+use crate::{compare, write_atomic, Error, UpdateMode};
+
+/// The location of an `assert_inline_snapshot!` invocation, as reported by `file!()`, `line!()`
+/// and `column!()`.
+#[derive(Clone, Copy, Debug)]
+pub struct Location {
+    pub manifest_dir: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Lines added (or removed, if negative) by earlier updates to each source file, keyed by the
+/// original line of the updated snapshot, so that later updates in the same run can find their
+/// invocation even though `line!()` refers to the file as it was compiled.
+static LINE_OFFSETS: Mutex<Option<HashMap<PathBuf, LineOffsets>>> = Mutex::new(None);
+
+type LineOffsets = Vec<(u32, isize)>;
+
+/// Returns the expected value of the snapshot `literal`, whose value is `expected`. Raw string
+/// literals starting with a newline are dedented.
+pub(crate) fn expected_value(literal: &str, expected: &str) -> String {
+    if literal.starts_with('r') {
+        dedent(expected)
+    } else {
+        expected.to_owned()
+    }
+}
+
+pub(crate) fn check_inline_snapshot(
+    actual: &str,
+    expected: &str,
+    location: &Location,
+    show_diff: bool,
+) -> Result<(), Error> {
+    let (options, mode) = with_current(|settings| (settings.compare.clone(), settings.update_mode));
+    let diff = match compare(actual, expected, &options, show_diff) {
+        Ok(()) => return Ok(()),
+        Err(diff) => diff,
+    };
+    let path = source_path(location);
+    if mode == UpdateMode::Always {
+        update(&options.prepare(actual), location, &path)?;
+        Err(Error::Updated { path })
+    } else {
+        Err(Error::Difference { path, diff: Some(diff) })
+    }
+}
+
+fn update(actual: &str, location: &Location, path: &Path) -> Result<(), Error> {
+    let mut offsets = LINE_OFFSETS.lock().unwrap_or_else(|err| err.into_inner());
+    let offsets = offsets.get_or_insert_with(HashMap::new).entry(path.to_owned()).or_default();
+    let shift: isize = offsets
+        .iter()
+        .filter(|&&(line, _)| line < location.line)
+        .map(|&(_, shift)| shift)
+        .sum();
+    let line = (location.line as isize + shift) as usize;
+
+    let source = std::fs::read_to_string(path).map_err(Error::read(path))?;
+    let (updated, added_lines) = replace_literal(&source, line, location.column as usize, actual)
+        .ok_or_else(|| {
+            let message = format!("no inline snapshot found at line {}", line);
+            Error::write(path)(std::io::Error::new(std::io::ErrorKind::InvalidData, message))
+        })?;
+    write_atomic(path, updated.as_bytes())?;
+    offsets.push((location.line, added_lines));
+    Ok(())
+}
+
+/// `file!()` is relative to the workspace root, which may be any ancestor of the manifest
+/// directory.
+pub(crate) fn source_path(location: &Location) -> PathBuf {
+    Path::new(location.manifest_dir)
+        .ancestors()
+        .map(|dir| dir.join(location.file))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(location.file))
+}
+
+/// Replaces the snapshot literal of the macro invocation starting at the 1-based `line` and
+/// `column` with a literal for `actual`. Returns the new source and the number of lines added.
+fn replace_literal(source: &str, line: usize, column: usize, actual: &str) -> Option<(String, isize)> {
+    let line_start = source.split_inclusive('\n').take(line - 1).map(str::len).sum::<usize>();
+    let line_text = source[line_start..].lines().next()?;
+    let start = line_start + line_text.char_indices().nth(column - 1)?.0;
+    let (literal_start, literal_end) = find_literal(&source[start..])?;
+    let (literal_start, literal_end) = (start + literal_start, start + literal_end);
+
+    let literal = format_literal(actual, &line_text[..indentation(line_text)]);
+    let added_lines = literal.matches('\n').count() as isize
+        - source[literal_start..literal_end].matches('\n').count() as isize;
+
+    let mut updated = String::with_capacity(source.len() + literal.len());
+    updated.push_str(&source[..literal_start]);
+    updated.push_str(&literal);
+    updated.push_str(&source[literal_end..]);
+    Some((updated, added_lines))
+}
+
+/// Finds the byte range of the literal following the `@` in the macro invocation at the start of
+/// `source`, skipping over comments and literals in the asserted expression.
+fn find_literal(source: &str) -> Option<(usize, usize)> {
+    let bytes = source.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return None;
+                }
+            }
+            b'@' if depth == 1 => {
+                let start = i + 1 + source[i + 1..].len() - source[i + 1..].trim_start().len();
+                return Some((start, start + string_literal_len(&source[start..])?));
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i += source[i..].find('\n')?;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += source[i..].find("*/")? + 1;
+            }
+            b'\'' => {
+                // Skip char literals, but not lifetimes.
+                if bytes.get(i + 1) == Some(&b'\\') {
+                    i += 2 + source[i + 2..].find('\'')?;
+                } else if let Some(c) = source[i + 1..].chars().next() {
+                    if source[i + 1 + c.len_utf8()..].starts_with('\'') {
+                        i += c.len_utf8() + 1;
+                    }
+                }
+            }
+            b'"' | b'r' | b'b' => {
+                let previous = source[..i].chars().next_back();
+                if !previous.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    if let Some(len) = string_literal_len(&source[i..]) {
+                        i += len - 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Returns the length of the (raw, byte, or plain) string literal at the start of `source`.
+fn string_literal_len(source: &str) -> Option<usize> {
+    let prefix = source.len() - source.trim_start_matches(&['b', 'r'][..]).len();
+    let rest = &source[prefix..];
+    if source[..prefix].contains('r') {
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let body = rest[hashes..].strip_prefix('"')?;
+        Some(prefix + hashes + 1 + body.find(&terminator)? + terminator.len())
+    } else {
+        let body = rest.strip_prefix('"')?;
+        let mut chars = body.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => return Some(prefix + i + 2),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Formats `value` as a string literal. Multi-line values use a raw string literal starting with
+/// a newline, with each line indented one level deeper than `indentation`.
+fn format_literal(value: &str, indentation: &str) -> String {
+    if value.contains('\r') {
+        return format!("{:?}", value);
+    }
+    if value.contains('\n') {
+        let mut body = String::from("\n");
+        for line in value.split('\n') {
+            if !line.is_empty() {
+                body.push_str(indentation);
+                body.push_str("    ");
+                body.push_str(line);
+            }
+            body.push('\n');
+        }
+        body.push_str(indentation);
+        if dedent(&body) == value {
+            raw_literal(&body)
+        } else {
+            format!("{:?}", value)
+        }
+    } else if value.contains(&['"', '\\'][..]) {
+        raw_literal(value)
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
+fn raw_literal(body: &str) -> String {
+    let mut hashes = String::new();
+    while body.contains(&format!("\"{}", hashes)) {
+        hashes.push('#');
+    }
+    format!("r{0}\"{1}\"{0}", hashes, body)
+}
+
+/// Strips the leading newline, the trailing indentation and the common indentation of a raw
+/// multi-line snapshot literal. Literals which don't start with a newline are left unchanged.
+fn dedent(literal: &str) -> String {
+    let body = match literal.strip_prefix('\n') {
+        Some(body) => body,
+        None => return literal.to_owned(),
+    };
+    let mut lines: Vec<&str> = body.split('\n').collect();
+    if lines.last().is_some_and(|line| indentation(line) == line.len()) {
+        lines.pop();
+    }
+    let common = lines
+        .iter()
+        .filter(|line| indentation(line) < line.len())
+        .map(|line| indentation(line))
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| &line[common.min(indentation(line))..])
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(&[' ', '\t'][..]).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dedent, format_literal, replace_literal};
+
+    #[test]
+    fn literals() {
+        assert_eq!(format_literal("hello", "    "), r#""hello""#);
+        assert_eq!(format_literal(r#"say "hi""#, ""), r##"r#"say "hi""#"##);
+        assert_eq!(format_literal("a\n  b\n", "  "), "r\"\n      a\n        b\n\n  \"");
+        assert_eq!(format_literal("  a\n  b", ""), r#""  a\n  b""#);
+        for value in ["a\n  b\n", "\"#\n\\", "\n\nx\n \n"] {
+            let literal = format_literal(value, "\t");
+            let body = &literal[literal.find('"').unwrap() + 1..literal.rfind('"').unwrap()];
+            assert_eq!(dedent(body), value);
+        }
+    }
+
+    #[test]
+    fn replace() {
+        let source = "fn test() {\n    assert_inline_snapshot!(f(\"@\", '@', /* @ */ x), @\"\");\n}\n";
+        let (updated, added) = replace_literal(source, 2, 5, "one\ntwo").unwrap();
+        assert_eq!(
+            updated,
+            "fn test() {\n    assert_inline_snapshot!(\
+             f(\"@\", '@', /* @ */ x), @r\"\n        one\n        two\n    \");\n}\n"
+        );
+        assert_eq!(added, 3);
+        let (updated, added) = replace_literal(&updated, 2, 5, "three").unwrap();
+        assert_eq!(updated, source.replace("@\"\"", "@\"three\""));
+        assert_eq!(added, -3);
+    }
+
+    #[test]
+    fn assert_inline_snapshot() {
+        crate::assert_inline_snapshot!("hello world", @"hello world");
+        crate::assert_inline_snapshot!("multiple\n  lines\n", @r"
+            multiple
+              lines
+
+        ");
+    }
+}
@@ -1,91 +1,421 @@
-use std::fs::{File, OpenOptions};
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::fs::File;
 use std::io::{self, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use difference::Changeset;
 use thiserror::Error;
 
+use header::snapshot_contents;
+
+mod binary;
+mod config;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "ron"))]
+mod content;
+mod diff;
+mod filter;
+mod header;
+#[cfg(feature = "image")]
+mod image;
+mod inline;
+mod lock;
+#[doc(hidden)]
+pub mod macros;
+mod manifest;
+mod normalize;
+mod pending;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "ron"))]
+mod redaction;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "ron"))]
+mod serialization;
+mod settings;
+
+pub use binary::{check_binary_snapshot, check_binary_snapshot_no_diff, hex_diff};
+pub use config::CONFIG_FILE;
+pub use diff::{
+    render_diff, render_diff_with, Algorithm, ChangeTag, Color, Diff, DiffLine, DiffOptions, Granularity, Highlight,
+    Hunk, DIFF_VAR,
+};
+pub use filter::Filters;
+pub use header::{Metadata, FORMAT_VERSION};
+#[cfg(feature = "image")]
+pub use image::{check_image_snapshot, ImageOptions};
+pub use manifest::{find_unreferenced_snapshots, UnreferencedSnapshots, MANIFEST_DIR_VAR};
+pub use normalize::{FinalNewline, Normalize};
+pub use pending::{find_pending_snapshots, pending_path, PendingSnapshot};
+#[cfg(feature = "json")]
+pub use serialization::check_json_snapshot;
+#[cfg(feature = "ron")]
+pub use serialization::check_ron_snapshot;
+#[cfg(feature = "toml")]
+pub use serialization::check_toml_snapshot;
+#[cfg(feature = "yaml")]
+pub use serialization::check_yaml_snapshot;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "ron"))]
+pub use redaction::{dynamic_redaction, Redaction, Redactions};
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "ron"))]
+pub use serialization::{check_redacted_snapshot, check_serialized_snapshot, Format};
+pub use settings::{Settings, UpdateMode};
+
 const UPDATE_SNAPSHOTS_VAR: &str = "UPDATE_SNAPSHOTS";
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Created new snapshot")]
-    Created,
-    #[error("Updated snapshot")]
-    Updated,
-    #[error("Difference between actual and expected")]
-    Difference,
-    #[error("Error opening file: {0}")]
-    File(#[source] io::Error),
-    #[error("Error reading file: {0}")]
-    Read(#[source] io::Error),
-    #[error("Error writing file: {0}")]
-    Write(#[source] io::Error),
+    #[error("Created new snapshot {}", .path.display())]
+    Created { path: PathBuf },
+    #[error("Updated snapshot {}", .path.display())]
+    Updated { path: PathBuf },
+    #[error("Wrote new snapshot {} to pending file", .path.display())]
+    Pending { path: PathBuf },
+    /// The snapshot at `path` was asserted by more than one test, `test` being the first.
+    #[error("Snapshot {} was already asserted by test `{test}`", .path.display())]
+    Duplicate { path: PathBuf, test: String },
+    /// The snapshot at `path` doesn't exist, and the update mode is `UpdateMode::No`.
+    #[error("Missing snapshot {}", .path.display())]
+    Missing { path: PathBuf },
+    /// The actual value differs from the snapshot at `path`. `diff` is the line diff for text
+    /// snapshots, and `None` for binary and image snapshots.
+    #[error("Difference between actual and expected in {}", .path.display())]
+    Difference { path: PathBuf, diff: Option<Box<Diff>> },
+    #[error("Error opening file {}: {source}", .path.display())]
+    File { path: PathBuf, source: io::Error },
+    #[error("Error reading file {}: {source}", .path.display())]
+    Read { path: PathBuf, source: io::Error },
+    #[error("Error writing file {}: {source}", .path.display())]
+    Write { path: PathBuf, source: io::Error },
+    #[error("Error serializing value: {0}")]
+    Serialize(String),
+    #[error("Invalid redaction selector {0}")]
+    Selector(String),
+    #[error("Error decoding or encoding image: {0}")]
+    Image(String),
+}
+
+impl Error {
+    /// The path of the snapshot, or other file, which the error is about.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Created { path }
+            | Error::Updated { path }
+            | Error::Pending { path }
+            | Error::Duplicate { path, .. }
+            | Error::Missing { path }
+            | Error::Difference { path, .. }
+            | Error::File { path, .. }
+            | Error::Read { path, .. }
+            | Error::Write { path, .. } => Some(path),
+            Error::Serialize(_) | Error::Selector(_) | Error::Image(_) => None,
+        }
+    }
+
+    pub(crate) fn file(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+        move |source| Error::File {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    pub(crate) fn read(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+        move |source| Error::Read {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    pub(crate) fn write(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+        move |source| Error::Write {
+            path: path.to_owned(),
+            source,
+        }
+    }
 }
 
 pub fn check_snapshot(actual: &str, snapshot: impl AsRef<Path>) -> Result<(), Error> {
-    check_snapshot_diff_flag(actual, snapshot, true)
+    check_snapshot_diff_flag(actual, snapshot, None, true)
 }
 
 pub fn check_snapshot_no_diff(actual: &str, snapshot: impl AsRef<Path>) -> Result<(), Error> {
-    check_snapshot_diff_flag(actual, snapshot, false)
+    check_snapshot_diff_flag(actual, snapshot, None, false)
+}
+
+/// Like `check_snapshot`, but writes `metadata` to the header of the snapshot file when it is
+/// created or updated.
+pub fn check_snapshot_with_metadata(
+    actual: &str,
+    snapshot: impl AsRef<Path>,
+    metadata: &Metadata,
+) -> Result<(), Error> {
+    check_snapshot_diff_flag(actual, snapshot, Some(metadata), true)
 }
 
-fn check_snapshot_diff_flag(actual: &str, snapshot: impl AsRef<Path>, show_diff: bool) -> Result<(), Error> {
-    if !snapshot.as_ref().exists() {
+/// Like `check_snapshot`, but renders differences with `options` rather than the current
+/// settings.
+pub fn check_snapshot_with_diff_options(
+    actual: &str,
+    snapshot: impl AsRef<Path>,
+    options: &DiffOptions,
+) -> Result<(), Error> {
+    let options = CompareOptions {
+        diff: *options,
+        ..settings::with_current(|settings| settings.compare.clone())
+    };
+    check_text(actual, snapshot.as_ref(), &options)
+}
+
+/// Like `check_snapshot`, but also filters, normalises and compares the snapshot with `options`,
+/// on top of the current settings (see `CompareOptions::with_base`). The filtered and normalised
+/// value is what gets written when the snapshot is created or updated.
+pub fn check_snapshot_with_options(
+    actual: &str,
+    snapshot: impl AsRef<Path>,
+    options: &CompareOptions,
+) -> Result<(), Error> {
+    let options = settings::with_current(|settings| options.with_base(&settings.compare));
+    check_text(actual, snapshot.as_ref(), &options)
+}
+
+fn check_text(actual: &str, snapshot: &Path, options: &CompareOptions) -> Result<(), Error> {
+    let text = Text {
+        actual,
+        metadata: None,
+        options: Some(options),
+    };
+    check_contents(&text, snapshot, true)
+}
+
+pub fn check_debug_snapshot(actual: &impl Debug, snapshot: impl AsRef<Path>) -> Result<(), Error> {
+    check_snapshot_with_metadata(&format_debug(actual), snapshot, &Metadata::debug())
+}
+
+pub(crate) fn format_debug(value: &dyn Debug) -> String {
+    format!("{:#?}\n", value)
+}
+
+/// The actual value of a snapshot, which knows how to write itself to a snapshot file and how to
+/// compare itself to the contents of an existing one.
+pub(crate) trait Contents {
+    fn contents(&self) -> Cow<'_, [u8]>;
+
+    fn compare(&self, expected: &[u8], snapshot: &Path, show_diff: bool) -> Result<(), Error>;
+
+    /// Called after the snapshot is overwritten with these contents, to remove anything a failed
+    /// comparison left behind.
+    fn written(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct Text<'a> {
+    actual: &'a str,
+    metadata: Option<&'a Metadata>,
+    options: Option<&'a CompareOptions>,
+}
+
+impl Text<'_> {
+    fn options(&self) -> CompareOptions {
+        match self.options {
+            Some(options) => options.clone(),
+            None => settings::with_current(|settings| settings.compare.clone()),
+        }
+    }
+}
+
+impl Contents for Text<'_> {
+    fn contents(&self) -> Cow<'_, [u8]> {
+        match self.options().prepare(self.actual) {
+            Cow::Borrowed(actual) => match snapshot_contents(actual, self.metadata) {
+                Cow::Borrowed(contents) => Cow::Borrowed(contents.as_bytes()),
+                Cow::Owned(contents) => Cow::Owned(contents.into_bytes()),
+            },
+            Cow::Owned(actual) => Cow::Owned(snapshot_contents(&actual, self.metadata).into_owned().into_bytes()),
+        }
+    }
+
+    fn compare(&self, expected: &[u8], snapshot: &Path, show_diff: bool) -> Result<(), Error> {
+        let expected = std::str::from_utf8(expected)
+            .map_err(|err| Error::read(snapshot)(io::Error::new(io::ErrorKind::InvalidData, err)))?;
+        let (_, expected) = Metadata::parse(expected);
+        compare(self.actual, expected, &self.options(), show_diff).map_err(|diff| Error::Difference {
+            path: snapshot.to_owned(),
+            diff: Some(diff),
+        })
+    }
+}
+
+pub(crate) fn check_snapshot_diff_flag(
+    actual: &str,
+    snapshot: impl AsRef<Path>,
+    metadata: Option<&Metadata>,
+    show_diff: bool,
+) -> Result<(), Error> {
+    let text = Text {
+        actual,
+        metadata,
+        options: None,
+    };
+    check_contents(&text, snapshot.as_ref(), show_diff)
+}
+
+pub(crate) fn check_contents(actual: &dyn Contents, snapshot: &Path, show_diff: bool) -> Result<(), Error> {
+    manifest::record(snapshot)?;
+    lock::claim(snapshot)?;
+    let mode = settings::with_current(|settings| settings.update_mode);
+    // Only checks which may write need the lock, as readers see either version of a snapshot.
+    let may_write = match mode {
+        UpdateMode::Always | UpdateMode::Pending => true,
+        UpdateMode::New => !snapshot.exists(),
+        UpdateMode::No => false,
+    };
+    let _lock = if may_write { Some(lock::lock(snapshot)?) } else { None };
+    if mode == UpdateMode::Pending {
+        pending::check_pending(actual, snapshot, show_diff)
+    } else if !snapshot.exists() && mode == UpdateMode::No {
+        Err(Error::Missing {
+            path: snapshot.to_owned(),
+        })
+    } else if !snapshot.exists() {
         create(actual, snapshot, show_diff)
-    } else if std::env::var(UPDATE_SNAPSHOTS_VAR).is_ok() {
+    } else if mode == UpdateMode::Always {
         check_and_update(actual, snapshot, show_diff)
     } else {
         check(actual, snapshot, show_diff)
     }
 }
 
-fn check(actual: &str, snapshot: impl AsRef<Path>, show_diff: bool) -> Result<(), Error> {
-    let mut file = File::open(snapshot).map_err(Error::File)?;
-    let expected = read_to_string(&mut file)?;
+fn check(actual: &dyn Contents, snapshot: &Path, show_diff: bool) -> Result<(), Error> {
+    let mut file = File::open(snapshot).map_err(Error::file(snapshot))?;
+    let expected = read(&mut file, snapshot)?;
 
-    compare(actual, &expected, show_diff)
+    actual.compare(&expected, snapshot, show_diff)
 }
 
-fn create(actual: &str, snapshot: impl AsRef<Path>, show_diff: bool) -> Result<(), Error> {
-    let mut file = File::create(snapshot).map_err(Error::File)?;
-    file.write(actual.as_bytes()).map_err(Error::Write)?;
+fn create(actual: &dyn Contents, snapshot: &Path, show_diff: bool) -> Result<(), Error> {
+    write_atomic(snapshot, &actual.contents())?;
 
-    let _ = compare(actual, "", show_diff);
-    Err(Error::Created)
+    if show_diff {
+        let _ = actual.compare(b"", snapshot, show_diff);
+    }
+    Err(Error::Created {
+        path: snapshot.to_owned(),
+    })
 }
 
-fn check_and_update(actual: &str, snapshot: impl AsRef<Path>, show_diff: bool) -> Result<(), Error> {
-    if check(actual, &snapshot, show_diff).is_err() {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(snapshot)
-            .map_err(Error::File)?;
-        file.write(actual.as_bytes()).map_err(Error::Write)?;
-        Err(Error::Updated)
+fn check_and_update(actual: &dyn Contents, snapshot: &Path, show_diff: bool) -> Result<(), Error> {
+    if check(actual, snapshot, show_diff).is_err() {
+        write_atomic(snapshot, &actual.contents())?;
+        actual.written()?;
+        Err(Error::Updated {
+            path: snapshot.to_owned(),
+        })
     } else {
         Ok(())
     }
 }
 
-fn compare(actual: &str, expected: &str, show_diff: bool) -> Result<(), Error> {
-    let diff = Changeset::new(expected, actual, "");
-    if diff.distance == 0 {
+/// Options for comparing text snapshots, or text compared with `compare_text`.
+#[derive(Clone, Debug, Default)]
+pub struct CompareOptions {
+    pub diff: DiffOptions,
+    /// Applied to the actual value only, since the expected value was filtered when written.
+    pub filters: Filters,
+    pub normalize: Normalize,
+}
+
+impl CompareOptions {
+    /// Reads the options from the project configuration and the environment, as file-based
+    /// snapshots do.
+    pub fn from_env() -> Self {
+        Settings::from_env().compare
+    }
+
+    /// These options applied on top of `base`: `base`'s filters run first, anything either
+    /// normalises is normalised, and the diff options are `base`'s unless these aren't the
+    /// defaults, in which case `SNAPSHOT_DIFF` still overrides them.
+    pub(crate) fn with_base(&self, base: &CompareOptions) -> CompareOptions {
+        CompareOptions {
+            diff: if self.diff == DiffOptions::default() {
+                base.diff
+            } else {
+                self.diff.with_env()
+            },
+            filters: base.filters.clone().then(&self.filters),
+            normalize: base.normalize.union(self.normalize),
+        }
+    }
+
+    /// Filters and normalises the actual value, as it is compared and written.
+    pub(crate) fn prepare<'a>(&self, actual: &'a str) -> Cow<'a, str> {
+        match self.filters.apply(actual) {
+            Cow::Borrowed(actual) => self.normalize.apply(actual),
+            Cow::Owned(actual) => Cow::Owned(self.normalize.apply(&actual).into_owned()),
+        }
+    }
+}
+
+/// Compares `actual` to `expected` as a text snapshot would be compared to its file, without
+/// touching the filesystem. `actual` is filtered, both are normalised, and the diff is between
+/// the resulting values.
+pub fn compare_text(expected: &str, actual: &str, options: &CompareOptions) -> Result<(), Diff> {
+    let expected = options.normalize.apply(expected);
+    let actual = options.prepare(actual);
+    if actual == expected {
         Ok(())
     } else {
-        if show_diff {
-            eprintln!("{}", diff);
-        }
-        Err(Error::Difference)
+        Err(Diff::new(&expected, &actual, &options.diff))
+    }
+}
+
+/// Like `compare_text`, but prints the diff if `show_diff` is set. The diff is built either way,
+/// so that callers which don't print it still get it in the error.
+fn compare(actual: &str, expected: &str, options: &CompareOptions, show_diff: bool) -> Result<(), Box<Diff>> {
+    let diff = match compare_text(expected, actual, options) {
+        Ok(()) => return Ok(()),
+        Err(diff) => Box::new(diff),
+    };
+    if show_diff {
+        eprint!("{}", diff);
     }
+    Err(diff)
 }
 
-fn read_to_string(file: &mut File) -> Result<String, Error> {
+/// Writes `contents` to `path` by writing and syncing a temporary file in the same directory,
+/// then renaming it over `path`, so that readers see either the old or the new contents.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir).map_err(Error::file(dir))?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp = dir.join(format!(".{}.{}-{}.tmp", file_name, std::process::id(), count));
+
+    let result = File::create(&temp)
+        .map_err(Error::file(&temp))
+        .and_then(|mut file| {
+            file.write_all(contents).map_err(Error::write(&temp))?;
+            file.sync_all().map_err(Error::write(&temp))
+        })
+        .and_then(|()| std::fs::rename(&temp, path).map_err(Error::write(path)));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result?;
+    // Syncing the directory makes the rename itself durable. Not all platforms allow opening a
+    // directory as a file, so failures are ignored.
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn read(file: &mut File, path: &Path) -> Result<Vec<u8>, Error> {
     let buffer_len = file.metadata().map(|m| m.len() as usize + 1).unwrap_or(0);
-    let mut buffer = String::with_capacity(buffer_len);
-    file.read_to_string(&mut buffer).map_err(Error::Read)?;
+    let mut buffer = Vec::with_capacity(buffer_len);
+    file.read_to_end(&mut buffer).map_err(Error::read(path))?;
     Ok(buffer)
 }
 
@@ -93,41 +423,168 @@ fn read_to_string(file: &mut File) -> Result<String, Error> {
 mod tests {
     #[test]
     fn test_compare() {
-        super::compare("hello world", "hello, world!", false).unwrap_err();
-        super::compare("hello world", "hello world", false).unwrap();
+        let options = super::CompareOptions::default();
+        let diff = super::compare("hello world", "hello, world!", &options, false).unwrap_err();
+        assert_eq!((diff.deletions, diff.insertions), (1, 1));
+        super::compare("hello world", "hello world", &options, false).unwrap();
         super::compare(
             "this string\nhas multiple\nline",
             "this string\nhas multiple\nlines",
+            &options,
             false,
         )
         .unwrap_err();
     }
 
+    #[test]
+    fn compare_text() {
+        let options = super::CompareOptions::default();
+        super::compare_text("same\n", "same\n", &options).unwrap();
+        let diff = super::compare_text("one\ntwo\n", "one\n2\n", &options).unwrap_err();
+        assert_eq!((diff.deletions, diff.insertions), (1, 1));
+        assert_eq!(diff.hunks[0].lines[1].text, "two\n");
+    }
+
     #[test]
     fn snapshot() {
-        use super::Error;
-        std::env::remove_var(super::UPDATE_SNAPSHOTS_VAR);
+        use super::{Error, Settings, UpdateMode};
         let create_file = std::path::Path::new("snapshots/create.snap");
         if create_file.exists() {
             std::fs::remove_file(create_file).unwrap();
         }
-        match super::check_snapshot("hello world", create_file) {
-            Err(Error::Created) => {}
+        Settings::default().update_mode(UpdateMode::No).bind(|| {
+            match super::check_snapshot("hello world", create_file) {
+                Err(Error::Missing { path }) => assert_eq!(path, create_file),
+                other => panic!("Expected `Err(Missing)`, got `{:?}`", other),
+            }
+            assert!(!create_file.exists());
+        });
+        Settings::default().update_mode(UpdateMode::New).bind(|| {
+            match super::check_snapshot("hello world", create_file) {
+                Err(Error::Created { .. }) => {}
+                other => panic!("Expected `Err(Created)`, got `{:?}`", other),
+            }
+            super::check_snapshot("hello world", create_file).unwrap();
+
+            match super::check_snapshot("hello world", "snapshots/difference.snap") {
+                Err(Error::Difference { path, diff: Some(diff) }) => {
+                    assert_eq!(path, std::path::Path::new("snapshots/difference.snap"));
+                    assert_eq!((diff.expected.as_str(), diff.actual.as_str()), ("some other text\n", "hello world"));
+                    assert_eq!((diff.deletions, diff.insertions, diff.hunks.len()), (1, 1, 1));
+                }
+                other => panic!("Expected `Err(Difference)`, got `{:?}`", other),
+            }
+        });
+
+        Settings::default().update_mode(UpdateMode::Always).bind(|| {
+            match super::check_snapshot("hello world!", create_file) {
+                Err(Error::Updated { .. }) => {}
+                other => panic!("Expected `Err(Updated)`, got `{:?}`", other),
+            }
+            super::check_snapshot("hello world!", create_file).unwrap();
+        });
+        std::fs::remove_file(create_file).unwrap();
+    }
+
+    #[test]
+    fn normalized_snapshot() {
+        let options = super::CompareOptions {
+            normalize: super::Normalize::all(),
+            ..super::CompareOptions::default()
+        };
+        let snapshot = std::path::Path::new("target/normalize-test/normalized.snap");
+        if snapshot.exists() {
+            std::fs::remove_file(snapshot).unwrap();
+        }
+        let new = super::Settings::default().update_mode(super::UpdateMode::New);
+        match new.bind(|| super::check_snapshot_with_options("one  \r\ntwo", snapshot, &options)) {
+            Err(super::Error::Created { .. }) => {}
             other => panic!("Expected `Err(Created)`, got `{:?}`", other),
         }
-        super::check_snapshot("hello world", create_file).unwrap();
+        assert_eq!(std::fs::read_to_string(snapshot).unwrap(), "one\ntwo\n");
 
-        match super::check_snapshot("hello world", "snapshots/difference.snap") {
-            Err(Error::Difference) => {}
-            other => panic!("Expected `Err(Difference)`, got `{:?}`", other),
+        std::fs::write(snapshot, "one\r\ntwo\t\r\n\r\n").unwrap();
+        super::check_snapshot_with_options("one\ntwo\n\n", snapshot, &options).unwrap();
+
+        let header = format!("---\r\nline: 3\r\nformat_version: {}\r\n---\r\n", super::FORMAT_VERSION);
+        std::fs::write(snapshot, header + "one\r\ntwo\r\n").unwrap();
+        super::check_snapshot_with_options("one\ntwo\n", snapshot, &options).unwrap();
+    }
+
+    #[test]
+    fn filtered_snapshot() {
+        let options = super::CompareOptions {
+            filters: super::Filters::new().add(r"took \d+ms", "took [time]"),
+            ..super::CompareOptions::default()
+        };
+        super::compare_text("took [time]\n", "took 17ms\n", &options).unwrap();
+
+        let snapshot = std::path::Path::new("target/filter-test/filtered.snap");
+        if snapshot.exists() {
+            std::fs::remove_file(snapshot).unwrap();
         }
+        let settings = super::Settings::default()
+            .filters(super::Filters::new().add(r"\d+ms", "[ms]").add("user-[0-9]+", "[user]"))
+            .normalize(super::Normalize::all());
+        let new = settings.clone().update_mode(super::UpdateMode::New);
+        match new.bind(|| super::check_snapshot_with_options("user-1 took 3ms  ", snapshot, &options)) {
+            Err(super::Error::Created { .. }) => {}
+            other => panic!("Expected `Err(Created)`, got `{:?}`", other),
+        }
+        assert_eq!(std::fs::read_to_string(snapshot).unwrap(), "[user] took [ms]\n");
+        settings.bind(|| super::check_snapshot_with_options("user-2 took 250ms\n", snapshot, &options)).unwrap();
+    }
 
-        std::env::set_var(super::UPDATE_SNAPSHOTS_VAR, "1");
-        match super::check_snapshot("hello world!", create_file) {
-            Err(Error::Updated) => {}
-            other => panic!("Expected `Err(Updated)`, got `{:?}`", other),
+    #[test]
+    fn front_matter_snapshot() {
+        let snapshot = std::path::Path::new("target/front-matter-test/front_matter.snap");
+        let _ = std::fs::remove_file(snapshot);
+        let actual = "---\ntitle: x\n---\nbody\n";
+        let new = super::Settings::default().update_mode(super::UpdateMode::New);
+        match new.bind(|| super::check_snapshot_no_diff(actual, snapshot)) {
+            Err(super::Error::Created { .. }) => {}
+            other => panic!("Expected `Err(Created)`, got `{:?}`", other),
         }
-        super::check_snapshot("hello world!", create_file).unwrap();
-        std::fs::remove_file(create_file).unwrap();
+        super::check_snapshot_no_diff(actual, snapshot).unwrap();
+        match super::check_snapshot_no_diff("---\ntitle: y\n---\nbody\n", snapshot) {
+            Err(super::Error::Difference { diff: Some(diff), .. }) => {
+                assert_eq!(diff.hunks[0].lines[1].text, "title: x\n")
+            }
+            other => panic!("Expected `Err(Difference)` with a diff, got `{:?}`", other),
+        }
+    }
+
+    #[test]
+    fn header_snapshot() {
+        let snapshot = std::path::Path::new("target/front-matter-test/header.snap");
+        let _ = std::fs::remove_file(snapshot);
+        let actual = "---\nformat_version: 1\n---\nbody\n";
+        let new = super::Settings::default().update_mode(super::UpdateMode::New);
+        match new.bind(|| super::check_snapshot_no_diff(actual, snapshot)) {
+            Err(super::Error::Created { .. }) => {}
+            other => panic!("Expected `Err(Created)`, got `{:?}`", other),
+        }
+        super::check_snapshot_no_diff(actual, snapshot).unwrap();
+        match super::check_snapshot_no_diff("body\n", snapshot) {
+            Err(super::Error::Difference { .. }) => {}
+            other => panic!("Expected `Err(Difference)`, got `{:?}`", other),
+        }
+    }
+
+    #[test]
+    fn write_atomic() {
+        let dir = std::path::Path::new("target/atomic-test");
+        let _ = std::fs::remove_dir_all(dir);
+        let path = dir.join("nested/file.snap");
+        super::write_atomic(&path, b"first").unwrap();
+        super::write_atomic(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        let files: Vec<_> = std::fs::read_dir(dir.join("nested")).unwrap().map(|entry| entry.unwrap()).collect();
+        assert_eq!(files.len(), 1, "{:?}", files);
+    }
+
+    #[test]
+    fn debug_snapshot() {
+        super::check_debug_snapshot(&Some((1, "one")), "snapshots/debug.snap").unwrap();
     }
 }
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{Error, MANIFEST_DIR_VAR};
+
+/// The test which first asserted each snapshot path in this process.
+static ASSERTED: Mutex<Option<HashMap<PathBuf, String>>> = Mutex::new(None);
+
+/// Records that the current test asserts `snapshot`, failing if another test already has.
+/// Assertions which can't be attributed to a test (see `current_test`) aren't recorded.
+///
+/// Within a process, claims are kept in memory. They are also shared with the other processes of
+/// the test run through a claims file (see `claims_path`), if there is one.
+pub(crate) fn claim(snapshot: &Path) -> Result<(), Error> {
+    let test = match current_test() {
+        Some(test) => test,
+        None => return Ok(()),
+    };
+    let path = std::env::current_dir().map_err(Error::file(snapshot))?.join(snapshot);
+    let duplicate = |first: String| Error::Duplicate {
+        path: snapshot.to_owned(),
+        test: first,
+    };
+
+    let mut asserted = ASSERTED.lock().unwrap_or_else(|err| err.into_inner());
+    let asserted = asserted.get_or_insert_with(HashMap::new);
+    match asserted.get(&path) {
+        Some(first) if *first == test => return Ok(()),
+        Some(first) => return Err(duplicate(first.clone())),
+        None => {}
+    }
+    let first = match claims_path() {
+        Some(claims) => claim_shared(&claims, &path, &test).map_err(Error::write(&claims))?,
+        None => test.clone(),
+    };
+    asserted.insert(path, first.clone());
+    if first == test {
+        Ok(())
+    } else {
+        Err(duplicate(first))
+    }
+}
+
+/// The name of the test running on the current thread. The test harness runs each test on a
+/// thread named after its path, such as `tests::name`; other threads, such as ones a test
+/// spawned or the workers of an async runtime, can't be attributed to a test. When nextest runs
+/// each test in its own process, every thread belongs to the test the process was started for.
+fn current_test() -> Option<String> {
+    if std::env::var("NEXTEST_EXECUTION_MODE").as_deref() == Ok("process-per-test") {
+        let test = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+        return Some(test.unwrap_or_else(|| format!("process {}", std::process::id())));
+    }
+    let thread = std::thread::current();
+    let name = thread.name()?;
+    let is_path = name.contains("::")
+        && name
+            .split("::")
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_'));
+    if is_path {
+        Some(name.to_owned())
+    } else {
+        None
+    }
+}
+
+/// The file through which the processes of a test run share their claims: in the manifest
+/// directory of `cargo snapshot prune`, which is cleared for each run, or else one per nextest
+/// run, as nextest runs tests in separate processes.
+fn claims_path() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(MANIFEST_DIR_VAR) {
+        return Some(PathBuf::from(dir).join("claims"));
+    }
+    let run = std::env::var("NEXTEST_RUN_ID").ok()?;
+    Some(state_dir().join(format!("{}.claims", run)))
+}
+
+/// The directory of the lock and claims files, so that `cargo clean` removes them:
+/// `snapshot-testing` in the target directory of the running test binary, which cargo marks
+/// with a `CACHEDIR.TAG`, or else in `target`.
+fn state_dir() -> PathBuf {
+    let exe = std::env::current_exe().ok();
+    let target = exe
+        .as_deref()
+        .and_then(|exe| exe.ancestors().skip(1).find(|dir| dir.join("CACHEDIR.TAG").is_file()))
+        .unwrap_or_else(|| Path::new("target"));
+    target.join("snapshot-testing")
+}
+
+/// Records in the claims file `claims`, a line of `<path>\t<test>` per snapshot, that `test`
+/// asserts `path`, unless another test already has. Returns the test which asserted it first.
+fn claim_shared(claims: &Path, path: &Path, test: &str) -> io::Result<String> {
+    if let Some(dir) = claims.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(claims)?;
+    file.lock()?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let path = path.to_string_lossy();
+    let first = contents
+        .lines()
+        .filter_map(|line| line.rsplit_once('\t'))
+        .find(|&(claimed, _)| claimed == path)
+        .map(|(_, first)| first.to_owned());
+    match first {
+        Some(first) => Ok(first),
+        None => {
+            file.seek(SeekFrom::End(0))?;
+            writeln!(file, "{}\t{}", path, test)?;
+            Ok(test.to_owned())
+        }
+    }
+}
+
+/// Takes an exclusive advisory lock on the directory of `snapshot`, which other processes
+/// checking snapshots in it wait for. The lock is released when the returned file is dropped.
+pub(crate) fn lock(snapshot: &Path) -> Result<File, Error> {
+    let path = lock_path(snapshot)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(Error::file(&path))?;
+    file.lock().map_err(Error::file(&path))?;
+    Ok(file)
+}
+
+/// The lock file for the directory of `snapshot`, which is kept in the target directory (see
+/// `state_dir`) so that it doesn't show up next to the snapshots. It is named after a hash of
+/// the directory which doesn't vary between builds, as `DefaultHasher`'s may, since processes
+/// built by different compilers can check the same snapshots.
+fn lock_path(snapshot: &Path) -> Result<PathBuf, Error> {
+    let dir = match snapshot.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir).map_err(Error::file(dir))?;
+    let dir = dir.canonicalize().map_err(Error::file(dir))?;
+    let hash = fnv1a(dir.as_os_str().as_encoded_bytes());
+    let state = state_dir();
+    std::fs::create_dir_all(&state).map_err(Error::file(&state))?;
+    Ok(state.join(format!("{:016x}.lock", hash)))
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::Error;
+
+    use super::{claim, claim_shared, fnv1a, lock, lock_path};
+
+    #[test]
+    fn duplicate() {
+        let snapshot = Path::new("target/lock-test/duplicate.snap");
+        claim(snapshot).unwrap();
+        claim(snapshot).unwrap();
+        std::thread::spawn(move || claim(snapshot)).join().unwrap().unwrap();
+        for name in ["worker-1", "worker"] {
+            let worker = std::thread::Builder::new().name(name.into()).spawn(move || claim(snapshot));
+            worker.unwrap().join().unwrap().unwrap();
+        }
+        let other = std::thread::Builder::new().name("tests::other_test".into()).spawn(move || claim(snapshot));
+        match other.unwrap().join().unwrap() {
+            Err(Error::Duplicate { path, test }) => {
+                assert_eq!(path, snapshot);
+                assert_eq!(Some(test.as_str()), std::thread::current().name());
+            }
+            other => panic!("Expected `Err(Duplicate)`, got `{:?}`", other),
+        }
+    }
+
+    #[test]
+    fn shared_claims() {
+        let claims = Path::new("target/lock-test/shared.claims");
+        if claims.exists() {
+            std::fs::remove_file(claims).unwrap();
+        }
+        let (a, b) = (Path::new("/snapshots/a.snap"), Path::new("/snapshots/b.snap"));
+        assert_eq!(claim_shared(claims, a, "tests::first").unwrap(), "tests::first");
+        assert_eq!(claim_shared(claims, a, "tests::second").unwrap(), "tests::first");
+        assert_eq!(claim_shared(claims, b, "tests::second").unwrap(), "tests::second");
+        assert_eq!(claim_shared(claims, a, "tests::first").unwrap(), "tests::first");
+    }
+
+    #[test]
+    fn exclusive() {
+        let snapshot = Path::new("target/lock-test/exclusive/a.snap");
+        let held = lock(snapshot).unwrap();
+        let other = std::fs::File::open(lock_path(Path::new("target/lock-test/exclusive/b.snap")).unwrap()).unwrap();
+        assert!(other.try_lock().is_err());
+        drop(held);
+        other.try_lock().unwrap();
+        assert!(lock_path(snapshot).unwrap().starts_with(std::fs::canonicalize("target").unwrap()));
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::binary::{hex_diff, Binary};
+use crate::inline::{check_inline_snapshot, expected_value, source_path};
+use crate::settings::with_current;
+use crate::{check_contents, check_snapshot_diff_flag, format_debug, Error, Metadata};
+
+pub use crate::inline::Location;
+
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($value:expr) => {
+        $crate::macros::assert_snapshot(
+            ::std::convert::AsRef::<str>::as_ref(&$value),
+            $crate::_snapshot_path!("snap"),
+            ::std::option::Option::Some($crate::_metadata!($value)),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! assert_debug_snapshot {
+    ($value:expr) => {
+        $crate::macros::assert_debug_snapshot(&$value, $crate::_snapshot_path!("snap"), $crate::_metadata!($value))
+    };
+}
+
+/// Compares a value to a string literal in the test source, e.g.
+/// `assert_inline_snapshot!(value, @"expected")`. When the update mode is `always`, a mismatched
+/// literal is rewritten in place.
+#[macro_export]
+macro_rules! assert_inline_snapshot {
+    ($value:expr, @$expected:literal) => {
+        $crate::macros::assert_inline_snapshot(
+            ::std::convert::AsRef::<str>::as_ref(&$value),
+            stringify!($expected),
+            $expected,
+            $crate::macros::Location {
+                manifest_dir: env!("CARGO_MANIFEST_DIR"),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            },
+        )
+    };
+}
+
+/// Runs `f` on every file matching `pattern`, e.g.
+/// `glob_snapshots!("inputs/*.txt", |path, contents| parse(contents))`, and snapshots the text
+/// it returns for each file. A relative pattern is relative to the directory of the calling
+/// source file. Every input is checked, and the failing inputs are reported together.
+#[macro_export]
+macro_rules! glob_snapshots {
+    ($pattern:expr, $f:expr) => {
+        $crate::macros::glob_snapshots(
+            ::std::convert::AsRef::<str>::as_ref(&$pattern),
+            $f,
+            $crate::macros::Location {
+                manifest_dir: env!("CARGO_MANIFEST_DIR"),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            },
+            module_path!(),
+            $crate::_function_name!(),
+        )
+    };
+}
+
+#[cfg(feature = "json")]
+#[macro_export]
+macro_rules! assert_json_snapshot {
+    ($value:expr $(, { $($selector:expr => $redaction:expr),* $(,)? })?) => {
+        $crate::macros::assert_serialized_snapshot(
+            &$value,
+            $crate::Format::Json,
+            $crate::Redactions::new()$($(.add($selector, $redaction))*)?,
+            $crate::_snapshot_path!("json"),
+        )
+    };
+}
+
+#[cfg(feature = "yaml")]
+#[macro_export]
+macro_rules! assert_yaml_snapshot {
+    ($value:expr $(, { $($selector:expr => $redaction:expr),* $(,)? })?) => {
+        $crate::macros::assert_serialized_snapshot(
+            &$value,
+            $crate::Format::Yaml,
+            $crate::Redactions::new()$($(.add($selector, $redaction))*)?,
+            $crate::_snapshot_path!("yaml"),
+        )
+    };
+}
+
+#[cfg(feature = "toml")]
+#[macro_export]
+macro_rules! assert_toml_snapshot {
+    ($value:expr $(, { $($selector:expr => $redaction:expr),* $(,)? })?) => {
+        $crate::macros::assert_serialized_snapshot(
+            &$value,
+            $crate::Format::Toml,
+            $crate::Redactions::new()$($(.add($selector, $redaction))*)?,
+            $crate::_snapshot_path!("toml"),
+        )
+    };
+}
+
+#[cfg(feature = "ron")]
+#[macro_export]
+macro_rules! assert_ron_snapshot {
+    ($value:expr $(, { $($selector:expr => $redaction:expr),* $(,)? })?) => {
+        $crate::macros::assert_serialized_snapshot(
+            &$value,
+            $crate::Format::Ron,
+            $crate::Redactions::new()$($(.add($selector, $redaction))*)?,
+            $crate::_snapshot_path!("ron"),
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _snapshot_path {
+    ($extension:expr) => {
+        $crate::macros::snapshot_path(
+            env!("CARGO_MANIFEST_DIR"),
+            module_path!(),
+            $crate::_function_name!(),
+            $extension,
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! assert_binary_snapshot {
+    ($value:expr) => {
+        $crate::macros::assert_binary_snapshot(
+            ::std::convert::AsRef::<[u8]>::as_ref(&$value),
+            $crate::_snapshot_path!("bin"),
+        )
+    };
+}
+
+#[cfg(feature = "image")]
+#[macro_export]
+macro_rules! assert_image_snapshot {
+    ($value:expr) => {
+        $crate::assert_image_snapshot!($value, ::std::default::Default::default())
+    };
+    ($value:expr, $options:expr) => {
+        $crate::macros::assert_image_snapshot(
+            ::std::convert::AsRef::<[u8]>::as_ref(&$value),
+            $crate::_snapshot_path!("png"),
+            &$options,
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _metadata {
+    ($value:expr) => {
+        $crate::Metadata {
+            source: ::std::option::Option::Some(file!().into()),
+            line: ::std::option::Option::Some(line!()),
+            expression: ::std::option::Option::Some(stringify!($value).into()),
+            ..::std::default::Default::default()
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _function_name {
+    () => {{
+        fn f() {}
+        fn type_name_of<T>(_: T) -> &'static str {
+            ::std::any::type_name::<T>()
+        }
+        type_name_of(f)
+    }};
+}
+
+/// Derives the path of the next snapshot for the test function `function`, which is the type
+/// name of a function item nested inside the test (see `_function_name!`), in the snapshot
+/// directory of the current settings.
+pub fn snapshot_path(manifest_dir: &str, module_path: &str, function: &str, extension: &str) -> PathBuf {
+    static COUNTERS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+    let function = test_function(function);
+    let count = {
+        let mut counters = COUNTERS.lock().unwrap_or_else(|err| err.into_inner());
+        let count = counters.get_or_insert_with(HashMap::new).entry(function.to_owned()).or_insert(0);
+        *count += 1;
+        *count
+    };
+    let mut file_name = snapshot_prefix(module_path, function);
+    if count > 1 {
+        file_name.push_str(&format!("-{}", count));
+    }
+    file_name.push('.');
+    file_name.push_str(extension);
+
+    with_current(|settings| settings.snapshot_path(manifest_dir, &file_name))
+}
+
+/// Strips the nested function item and any closures from `function`, leaving the test function.
+fn test_function(function: &str) -> &str {
+    let mut function = function.strip_suffix("::f").unwrap_or(function);
+    while let Some(outer) = function.strip_suffix("::{{closure}}") {
+        function = outer;
+    }
+    function
+}
+
+fn snapshot_prefix(module_path: &str, function: &str) -> String {
+    let name = function.rsplit("::").next().unwrap_or(function);
+    format!("{}__{}", module_path.replace("::", "__"), name)
+}
+
+pub fn glob_snapshots<S: AsRef<str>>(
+    pattern: &str,
+    mut f: impl FnMut(&Path, &str) -> S,
+    location: Location,
+    module_path: &str,
+    function: &str,
+) {
+    let source = source_path(&location);
+    let dir = source.parent().unwrap_or_else(|| Path::new("."));
+    let (base, full_pattern) = if Path::new(pattern).is_absolute() {
+        (glob_base(pattern), pattern.to_owned())
+    } else {
+        let escaped = glob::Pattern::escape(&dir.to_string_lossy());
+        (dir.join(glob_base(pattern)), format!("{}/{}", escaped, pattern))
+    };
+    let inputs: Vec<PathBuf> = glob::glob(&full_pattern)
+        .unwrap_or_else(|err| panic!("Invalid glob pattern `{}`: {}", pattern, err))
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .collect();
+    if inputs.is_empty() {
+        panic!("No files match `{}` in {}", pattern, dir.display());
+    }
+
+    let prefix = snapshot_prefix(module_path, test_function(function));
+    let metadata = Metadata {
+        source: Some(location.file.into()),
+        line: Some(location.line),
+        ..Metadata::default()
+    };
+    let mut failures = Vec::new();
+    for input in &inputs {
+        let name = input.strip_prefix(&base).unwrap_or(input).iter().map(|part| part.to_string_lossy());
+        let file_name = format!("{}@{}.snap", prefix, name.collect::<Vec<_>>().join("__"));
+        let snapshot = with_current(|settings| settings.snapshot_path(location.manifest_dir, &file_name));
+
+        let contents = match std::fs::read_to_string(input) {
+            Ok(contents) => contents,
+            Err(err) => {
+                failures.push(format!("{}: Error reading input: {}", input.display(), err));
+                continue;
+            }
+        };
+        let actual = match panic::catch_unwind(AssertUnwindSafe(|| f(input, &contents).as_ref().to_owned())) {
+            Ok(actual) => actual,
+            Err(payload) => {
+                let message = payload.downcast_ref::<&str>().copied();
+                match message.or_else(|| payload.downcast_ref::<String>().map(String::as_str)) {
+                    Some(message) => failures.push(format!("{}: panicked: {}", input.display(), message)),
+                    None => failures.push(format!("{}: panicked", input.display())),
+                }
+                continue;
+            }
+        };
+        match check_snapshot_diff_flag(&actual, &snapshot, Some(&metadata), false) {
+            Ok(()) => {}
+            Err(Error::Difference { diff: Some(diff), .. }) => failures.push(format!(
+                "{}: Snapshot `{}` does not match:\n{}",
+                input.display(),
+                snapshot.display(),
+                diff
+            )),
+            Err(err) => failures.push(format!("{}: {}", input.display(), err)),
+        }
+    }
+    if !failures.is_empty() {
+        panic!("{} of {} inputs failed:\n\n{}", failures.len(), inputs.len(), failures.join("\n\n"));
+    }
+}
+
+/// The directory of `pattern` before its first component containing a wildcard.
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| !component.as_os_str().to_string_lossy().contains(&['*', '?', '['][..]))
+        .collect()
+}
+
+pub fn assert_snapshot(actual: &str, snapshot: PathBuf, metadata: Option<Metadata>) {
+    match check_snapshot_diff_flag(actual, &snapshot, metadata.as_ref(), false) {
+        Ok(()) => {}
+        Err(Error::Difference { diff: Some(diff), .. }) => {
+            panic!("Snapshot `{}` does not match:\n{}", snapshot.display(), diff)
+        }
+        Err(err) => panic!("Snapshot `{}`: {}", snapshot.display(), err),
+    }
+}
+
+pub fn assert_inline_snapshot(actual: &str, literal: &str, expected: &str, location: Location) {
+    let expected = expected_value(literal, expected);
+    match check_inline_snapshot(actual, &expected, &location, false) {
+        Ok(()) => {}
+        Err(Error::Difference { diff: Some(diff), .. }) => panic!(
+            "Inline snapshot at {}:{} does not match:\n{}",
+            location.file, location.line, diff
+        ),
+        Err(err) => panic!("Inline snapshot at {}:{}: {}", location.file, location.line, err),
+    }
+}
+
+pub fn assert_binary_snapshot(actual: &[u8], snapshot: PathBuf) {
+    match check_contents(&Binary(actual), &snapshot, false) {
+        Ok(()) => {}
+        Err(Error::Difference { .. }) => {
+            let expected = std::fs::read(&snapshot).unwrap_or_default();
+            panic!("Snapshot `{}` does not match:\n{}", snapshot.display(), hex_diff(&expected, actual));
+        }
+        Err(err) => panic!("Snapshot `{}`: {}", snapshot.display(), err),
+    }
+}
+
+#[cfg(feature = "image")]
+pub fn assert_image_snapshot(actual: &[u8], snapshot: PathBuf, options: &crate::ImageOptions) {
+    match crate::image::check_image_snapshot_no_diff(actual, &snapshot, options) {
+        Ok(()) => {}
+        Err(Error::Difference { .. }) => panic!(
+            "Snapshot `{}` does not match, see {}",
+            snapshot.display(),
+            snapshot.with_extension("diff.png").display()
+        ),
+        Err(err) => panic!("Snapshot `{}`: {}", snapshot.display(), err),
+    }
+}
+
+pub fn assert_debug_snapshot(actual: &dyn Debug, snapshot: PathBuf, metadata: Metadata) {
+    let metadata = Metadata {
+        kind: Some("debug".into()),
+        ..metadata
+    };
+    assert_snapshot(&format_debug(actual), snapshot, Some(metadata))
+}
+
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "ron"))]
+pub fn assert_serialized_snapshot(
+    actual: &impl serde::Serialize,
+    format: crate::Format,
+    redactions: crate::Redactions,
+    snapshot: PathBuf,
+) {
+    match crate::serialization::serialize(actual, format, &redactions) {
+        Ok(actual) => assert_snapshot(&actual, snapshot, None),
+        Err(err) => panic!("Snapshot `{}`: {}", snapshot.display(), err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn snapshot_names() {
+        let first = crate::macros::snapshot_path("/crate", module_path!(), crate::_function_name!(), "snap");
+        let closure = || crate::macros::snapshot_path("/crate", module_path!(), crate::_function_name!(), "snap");
+        let second = closure();
+        assert_eq!(
+            first,
+            std::path::Path::new("/crate/snapshots/snapshot_testing__macros__tests__snapshot_names.snap")
+        );
+        assert_eq!(
+            second,
+            std::path::Path::new("/crate/snapshots/snapshot_testing__macros__tests__snapshot_names-2.snap")
+        );
+    }
+
+    #[test]
+    fn assert_snapshot() {
+        assert_snapshot!("hello world");
+        assert_snapshot!(String::from("hello again"));
+    }
+
+    #[test]
+    fn assert_snapshot_mismatch() {
+        use crate::{Settings, UpdateMode};
+
+        let snapshot = std::path::Path::new("target/macros-test/mismatch.snap");
+        std::fs::create_dir_all(snapshot.parent().unwrap()).unwrap();
+        std::fs::write(snapshot, "expected\n").unwrap();
+        let result = std::panic::catch_unwind(|| {
+            Settings::default()
+                .update_mode(UpdateMode::New)
+                .bind(|| super::assert_snapshot("actual\n", snapshot.to_owned(), None))
+        });
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("does not match") && message.contains("+actual"), "{}", message);
+    }
+
+    #[test]
+    fn assert_debug_snapshot() {
+        assert_debug_snapshot!(vec![Some('a'), None]);
+    }
+
+    #[test]
+    fn glob_snapshots() {
+        use crate::{Settings, UpdateMode};
+
+        let dir = std::env::current_dir().unwrap().join("target/glob-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("inputs/nested")).unwrap();
+        std::fs::write(dir.join("inputs/a.txt"), "one").unwrap();
+        std::fs::write(dir.join("inputs/nested/b.txt"), "two").unwrap();
+        let pattern = format!("{}/inputs/**/*.txt", dir.display());
+        let run = || {
+            let settings = Settings::default().snapshot_dir(dir.join("snapshots")).update_mode(UpdateMode::New);
+            let result = std::panic::catch_unwind(|| {
+                settings.bind(|| glob_snapshots!(pattern, |_path, contents| contents.to_uppercase()))
+            });
+            result.err().map(|err| *err.downcast::<String>().unwrap())
+        };
+
+        let created = run().unwrap();
+        assert!(created.starts_with("2 of 2 inputs failed"), "{}", created);
+        let snapshot = dir.join("snapshots/snapshot_testing__macros__tests__glob_snapshots@nested__b.txt.snap");
+        assert!(std::fs::read_to_string(snapshot).unwrap().ends_with("\nTWO"));
+        assert_eq!(run(), None);
+
+        std::fs::write(dir.join("inputs/a.txt"), "changed").unwrap();
+        let failed = run().unwrap();
+        assert!(failed.starts_with("1 of 2 inputs failed"), "{}", failed);
+        assert!(failed.contains("a.txt: Snapshot") && failed.contains("+CHANGED"), "{}", failed);
+
+        let settings = Settings::default().snapshot_dir(dir.join("snapshots")).update_mode(UpdateMode::New);
+        let result = std::panic::catch_unwind(|| {
+            settings.bind(|| {
+                glob_snapshots!(pattern, |path, contents| {
+                    if path.ends_with("b.txt") {
+                        panic!("bad input {}", contents);
+                    }
+                    contents.to_owned()
+                })
+            })
+        });
+        let panicked = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(panicked.contains("b.txt: panicked: bad input two"), "{}", panicked);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn assert_json_snapshot() {
+        assert_json_snapshot!(vec![(1, "one"), (2, "two")]);
+        assert_json_snapshot!(vec![(1, "one"), (2, "two")], { "[][1]" => "[redacted]" });
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::Error;
+
+/// When set, every snapshot path checked by this process is recorded in a manifest file in this
+/// directory, so that `cargo snapshot prune` can find snapshots which no test referenced.
+pub const MANIFEST_DIR_VAR: &str = "SNAPSHOT_MANIFEST_DIR";
+
+static MANIFEST: Mutex<Option<(PathBuf, File)>> = Mutex::new(None);
+
+/// Records `snapshot` in this process's manifest, if `SNAPSHOT_MANIFEST_DIR` is set.
+pub(crate) fn record(snapshot: &Path) -> Result<(), Error> {
+    let dir = match std::env::var_os(MANIFEST_DIR_VAR) {
+        Some(dir) => PathBuf::from(dir),
+        None => return Ok(()),
+    };
+    let mut manifest = MANIFEST.lock().unwrap_or_else(|err| err.into_inner());
+    if manifest.is_none() {
+        // Each process gets its own manifest, named after its test binary, e.g.
+        // `my_crate-0123456789abcdef.4321.manifest`.
+        let exe = std::env::current_exe().map_err(Error::file(&dir))?;
+        let stem = exe.file_stem().unwrap_or_default().to_string_lossy();
+        let path = dir.join(format!("{}.{}.manifest", stem, std::process::id()));
+        std::fs::create_dir_all(&dir).map_err(Error::file(&dir))?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(Error::file(&path))?;
+        *manifest = Some((path, file));
+    }
+    let snapshot = std::env::current_dir().map_err(Error::file(snapshot))?.join(snapshot);
+    let (path, file) = manifest.as_mut().unwrap();
+    writeln!(file, "{}", snapshot.display()).map_err(Error::write(path))
+}
+
+/// The snapshot files which none of the test processes that wrote manifests referenced.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnreferencedSnapshots {
+    /// Snapshots named after a test which ran without referencing them, e.g. `test-3.snap` after
+    /// the test's third assertion was removed. These are safe to delete.
+    pub stale: Vec<PathBuf>,
+    /// Snapshots named after a test which didn't run, e.g. because it is ignored, filtered out or
+    /// behind a disabled feature, and snapshots which aren't named after a test. These may still
+    /// be in use, so they should only be deleted by hand.
+    pub unknown: Vec<PathBuf>,
+}
+
+/// Finds the snapshot files which were not referenced by any of the test processes that wrote
+/// manifests to `manifest_dir`.
+///
+/// Only directories containing a referenced snapshot are searched. Snapshots named by the
+/// assertion macros (`<crate>__<module>__<test>`) are further scoped to the crates whose test
+/// binaries ran, so that running a single test binary doesn't flag the snapshots of the others.
+/// A test counts as having run if it referenced any snapshot named after it.
+pub fn find_unreferenced_snapshots(manifest_dir: impl AsRef<Path>) -> io::Result<UnreferencedSnapshots> {
+    let mut referenced = HashSet::new();
+    let mut crates = HashSet::new();
+    for entry in std::fs::read_dir(manifest_dir)? {
+        let path = entry?.path();
+        if path.extension() != Some("manifest".as_ref()) {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let stem = name.split('.').next().unwrap_or_default();
+        crates.insert(stem.rsplit_once('-').map_or(stem, |(name, _hash)| name).to_owned());
+        referenced.extend(std::fs::read_to_string(&path)?.lines().map(PathBuf::from));
+    }
+    let tests: HashSet<String> = referenced
+        .iter()
+        .filter_map(|path| test_name(&path.file_name()?.to_string_lossy()).map(str::to_owned))
+        .collect();
+
+    let dirs: HashSet<_> = referenced.iter().filter_map(|path| path.parent()).collect();
+    let mut unreferenced = UnreferencedSnapshots::default();
+    for dir in dirs {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let path = entry.path();
+            if !entry.file_type()?.is_file()
+                || name.starts_with('.')
+                || name.ends_with(".new")
+                || referenced.contains(&path)
+                || is_diff_image(&path)
+            {
+                continue;
+            }
+            match test_name(&name) {
+                Some(test) if tests.contains(test) => unreferenced.stale.push(path),
+                Some(test) if !crates.contains(test.split("__").next().unwrap_or_default()) => {}
+                _ => unreferenced.unknown.push(path),
+            }
+        }
+    }
+    unreferenced.stale.sort();
+    unreferenced.unknown.sort();
+    Ok(unreferenced)
+}
+
+/// Whether `path` is the image highlighting how an existing image snapshot differed, which is
+/// removed along with the failure rather than by pruning.
+fn is_diff_image(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".diff.png") && path.with_extension("").with_extension("png").exists()
+}
+
+/// Returns the `<crate>__<module>__<test>` prefix of a snapshot named by the assertion macros,
+/// which is followed by an optional `-<n>` counter or `@<input>`, and the extension.
+fn test_name(file_name: &str) -> Option<&str> {
+    let name = file_name.split(&['.', '@'][..]).next().unwrap_or_default();
+    let name = match name.rsplit_once('-') {
+        Some((test, count)) if !count.is_empty() && count.bytes().all(|b| b.is_ascii_digit()) => test,
+        _ => name,
+    };
+    if name.contains("__") {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_unreferenced_snapshots;
+
+    #[test]
+    fn unreferenced() {
+        let root = std::env::current_dir().unwrap().join("target/manifest-test");
+        if root.exists() {
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+        let snapshots = root.join("snapshots");
+        let manifests = root.join("manifests");
+        std::fs::create_dir_all(&snapshots).unwrap();
+        std::fs::create_dir_all(&manifests).unwrap();
+        for name in &[
+            "used.snap",
+            "unused.snap",
+            "lib__tests__used.snap",
+            "lib__tests__used-3.snap",
+            "lib__tests__used@input.txt.snap",
+            "lib__tests__renamed.snap",
+            "other__tests__unused.snap",
+            "unused.snap.new",
+            "lib__tests__used.png",
+            "lib__tests__used.diff.png",
+        ] {
+            std::fs::write(snapshots.join(name), "").unwrap();
+        }
+        std::fs::write(
+            manifests.join("lib-0123456789abcdef.42.manifest"),
+            format!(
+                "{}\n{}\n{}\n",
+                snapshots.join("used.snap").display(),
+                snapshots.join("lib__tests__used.snap").display(),
+                snapshots.join("lib__tests__used.png").display()
+            ),
+        )
+        .unwrap();
+
+        let unreferenced = find_unreferenced_snapshots(&manifests).unwrap();
+        assert_eq!(
+            unreferenced.stale,
+            vec![snapshots.join("lib__tests__used-3.snap"), snapshots.join("lib__tests__used@input.txt.snap")]
+        );
+        assert_eq!(
+            unreferenced.unknown,
+            vec![snapshots.join("lib__tests__renamed.snap"), snapshots.join("unused.snap")]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+
+/// How text snapshots are normalised before they are compared, and before they are written to
+/// snapshot files. Nothing is normalised by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Normalize {
+    /// Converts `\r\n` line endings to `\n`.
+    pub line_endings: bool,
+    /// Removes spaces and tabs from the end of each line.
+    pub trailing_whitespace: bool,
+    pub final_newline: FinalNewline,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FinalNewline {
+    /// Compares final newlines like any other text.
+    #[default]
+    Keep,
+    /// Removes all newlines from the end of the text.
+    Ignore,
+    /// Adds a newline to the end of the text if it doesn't end with one.
+    Require,
+}
+
+impl Normalize {
+    /// Normalises line endings and trailing whitespace, and requires a final newline.
+    pub fn all() -> Self {
+        Normalize {
+            line_endings: true,
+            trailing_whitespace: true,
+            final_newline: FinalNewline::Require,
+        }
+    }
+
+    /// Normalises what either `self` or `other` normalises, with `other`'s final newline
+    /// handling unless it keeps final newlines.
+    pub(crate) fn union(self, other: Normalize) -> Self {
+        Normalize {
+            line_endings: self.line_endings || other.line_endings,
+            trailing_whitespace: self.trailing_whitespace || other.trailing_whitespace,
+            final_newline: match other.final_newline {
+                FinalNewline::Keep => self.final_newline,
+                final_newline => final_newline,
+            },
+        }
+    }
+
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        if self.line_endings && text.contains("\r\n") {
+            text = Cow::Owned(text.replace("\r\n", "\n"));
+        }
+        if self.trailing_whitespace && text.split('\n').any(|line| trimmed(line).len() < line.len()) {
+            text = Cow::Owned(text.split('\n').map(trimmed).collect::<Vec<_>>().join("\n"));
+        }
+        match self.final_newline {
+            FinalNewline::Keep => {}
+            FinalNewline::Ignore => {
+                let len = text.trim_end_matches('\n').len();
+                text = match text {
+                    Cow::Borrowed(text) => Cow::Borrowed(&text[..len]),
+                    Cow::Owned(mut text) => {
+                        text.truncate(len);
+                        Cow::Owned(text)
+                    }
+                };
+            }
+            FinalNewline::Require if !text.ends_with('\n') => text.to_mut().push('\n'),
+            FinalNewline::Require => {}
+        }
+        text
+    }
+}
+
+/// Returns `line` without trailing spaces and tabs, keeping a `\r` line ending.
+fn trimmed(line: &str) -> Cow<'_, str> {
+    match line.strip_suffix('\r') {
+        Some(body) => Cow::Owned(format!("{}\r", body.trim_end_matches(&[' ', '\t'][..]))),
+        None => Cow::Borrowed(line.trim_end_matches(&[' ', '\t'][..])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{FinalNewline, Normalize};
+
+    #[test]
+    fn normalize() {
+        let text = "a \r\nb\t\r\nc\n\n";
+        assert!(matches!(Normalize::default().apply(text), Cow::Borrowed(t) if t == text));
+        let line_endings = Normalize {
+            line_endings: true,
+            ..Normalize::default()
+        };
+        assert_eq!(line_endings.apply(text), "a \nb\t\nc\n\n");
+        let trailing_whitespace = Normalize {
+            trailing_whitespace: true,
+            ..Normalize::default()
+        };
+        assert_eq!(trailing_whitespace.apply(text), "a\r\nb\r\nc\n\n");
+        let ignore = Normalize {
+            final_newline: FinalNewline::Ignore,
+            ..Normalize::default()
+        };
+        assert_eq!(ignore.apply(text), "a \r\nb\t\r\nc");
+        assert_eq!(Normalize::all().apply(text), "a\nb\nc\n\n");
+        assert_eq!(Normalize::all().apply("x"), "x\n");
+    }
+}
@@ -0,0 +1,171 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{check, write_atomic, Contents, Error};
+
+/// Returns the path of the pending snapshot for `snapshot`, which is `snapshot` with `.new`
+/// appended.
+pub fn pending_path(snapshot: impl AsRef<Path>) -> PathBuf {
+    let mut pending = snapshot.as_ref().as_os_str().to_owned();
+    pending.push(".new");
+    PathBuf::from(pending)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingSnapshot {
+    pub snapshot: PathBuf,
+    pub pending: PathBuf,
+}
+
+impl PendingSnapshot {
+    pub fn new(snapshot: impl Into<PathBuf>) -> Self {
+        let snapshot = snapshot.into();
+        let pending = pending_path(&snapshot);
+        PendingSnapshot { snapshot, pending }
+    }
+
+    /// Replaces the accepted snapshot with the pending one, removing the image highlighting how
+    /// an image snapshot differed from it.
+    pub fn accept(&self) -> io::Result<()> {
+        std::fs::rename(&self.pending, &self.snapshot)?;
+        let diff_image = self.snapshot.with_extension("diff.png");
+        if self.snapshot.extension() == Some("png".as_ref()) && diff_image.exists() {
+            std::fs::remove_file(diff_image)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the pending snapshot, leaving the accepted one untouched.
+    pub fn reject(&self) -> io::Result<()> {
+        std::fs::remove_file(&self.pending)
+    }
+}
+
+/// Recursively finds pending snapshots under `root`, skipping hidden directories and `target`.
+/// Pending snapshots are `.snap.new` files, or any `.new` file in a `snapshots` directory.
+pub fn find_pending_snapshots(root: impl AsRef<Path>) -> io::Result<Vec<PendingSnapshot>> {
+    let mut found = Vec::new();
+    let mut dirs = vec![root.as_ref().to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let in_snapshots_dir = dir.file_name() == Some("snapshots".as_ref());
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if entry.file_type()?.is_dir() {
+                if !name.starts_with('.') && name != "target" {
+                    dirs.push(entry.path());
+                }
+            } else if let Some(snapshot) = name.strip_suffix(".new") {
+                if in_snapshots_dir || snapshot.ends_with(".snap") {
+                    found.push(PendingSnapshot::new(dir.join(snapshot)));
+                }
+            }
+        }
+    }
+    found.sort_by(|a, b| a.snapshot.cmp(&b.snapshot));
+    Ok(found)
+}
+
+/// Checks `actual` against `snapshot`, writing it to the pending snapshot instead of touching
+/// `snapshot` if it is new or differs. A stale pending snapshot is removed if `actual` matches.
+pub(crate) fn check_pending(actual: &dyn Contents, snapshot: &Path, show_diff: bool) -> Result<(), Error> {
+    let pending = pending_path(snapshot);
+    let result = if snapshot.exists() {
+        check(actual, snapshot, show_diff)
+    } else {
+        if show_diff {
+            let _ = actual.compare(b"", snapshot, show_diff);
+        }
+        Err(Error::Pending {
+            path: snapshot.to_owned(),
+        })
+    };
+    match result {
+        Ok(()) if pending.exists() => std::fs::remove_file(&pending).map_err(Error::write(&pending)),
+        Err(Error::Difference { .. }) | Err(Error::Pending { .. }) => {
+            write_atomic(&pending, &actual.contents())?;
+            result
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{check_pending, find_pending_snapshots, pending_path, PendingSnapshot};
+    use crate::{Error, Text};
+
+    fn text(actual: &str) -> Text<'_> {
+        Text {
+            actual,
+            metadata: None,
+            options: None,
+        }
+    }
+
+    #[test]
+    fn pending() {
+        let snapshot = Path::new("snapshots/pending.snap");
+        let pending = pending_path(snapshot);
+        assert_eq!(pending, Path::new("snapshots/pending.snap.new"));
+        if pending.exists() {
+            std::fs::remove_file(&pending).unwrap();
+        }
+
+        match check_pending(&text("hello world"), snapshot, false) {
+            Err(Error::Pending { path }) => assert_eq!(path, snapshot),
+            other => panic!("Expected `Err(Pending)`, got `{:?}`", other),
+        }
+        assert!(!snapshot.exists());
+        assert_eq!(std::fs::read_to_string(&pending).unwrap(), "hello world");
+
+        let snapshot = Path::new("snapshots/difference.snap");
+        let pending = pending_path(snapshot);
+        match check_pending(&text("hello world"), snapshot, false) {
+            Err(Error::Difference { .. }) => {}
+            other => panic!("Expected `Err(Difference)`, got `{:?}`", other),
+        }
+        assert_eq!(std::fs::read_to_string(snapshot).unwrap(), "some other text\n");
+        assert_eq!(std::fs::read_to_string(&pending).unwrap(), "hello world");
+        check_pending(&text("some other text\n"), snapshot, false).unwrap();
+        assert!(!pending.exists());
+        std::fs::remove_file(pending_path("snapshots/pending.snap")).unwrap();
+    }
+
+    #[test]
+    fn review() {
+        let dir = Path::new("target/review-test/snapshots");
+        if dir.exists() {
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("a.snap"), "old").unwrap();
+        std::fs::write(dir.join("a.snap.new"), "new").unwrap();
+        std::fs::write(dir.join("b.json.new"), "{}").unwrap();
+        std::fs::write(dir.join("c.snap"), "unchanged").unwrap();
+        std::fs::write(dir.join("d.png"), "old").unwrap();
+        std::fs::write(dir.join("d.png.new"), "new").unwrap();
+        std::fs::write(dir.join("d.diff.png"), "diff").unwrap();
+
+        let found = find_pending_snapshots("target/review-test").unwrap();
+        assert_eq!(
+            found,
+            vec![
+                PendingSnapshot::new(dir.join("a.snap")),
+                PendingSnapshot::new(dir.join("b.json")),
+                PendingSnapshot::new(dir.join("d.png"))
+            ]
+        );
+        found[0].accept().unwrap();
+        found[1].reject().unwrap();
+        found[2].accept().unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("a.snap")).unwrap(), "new");
+        assert!(!dir.join("b.json").exists());
+        assert!(!dir.join("d.diff.png").exists());
+        assert!(find_pending_snapshots("target/review-test").unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,271 @@
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::content::Content;
+use crate::Error;
+
+/// A list of redactions applied to a serialized value before it is compared to its snapshot.
+///
+/// Selectors are paths into the value, made up of the following segments:
+///
+/// - `.key` or `["key"]` selects a key of a map,
+/// - `.*` selects every key of a map,
+/// - `[3]` selects an element of a sequence and `[]` selects every element,
+/// - `.**` selects any number of nested keys or elements, including none.
+///
+/// For example, `.users[].created_at` selects the `created_at` key of every user, and `.**.id`
+/// selects every `id` key at any depth.
+#[derive(Default)]
+pub struct Redactions {
+    rules: Vec<(String, Redaction)>,
+}
+
+impl Redactions {
+    pub fn new() -> Self {
+        Redactions::default()
+    }
+
+    pub fn add(mut self, selector: impl Into<String>, redaction: impl Into<Redaction>) -> Self {
+        self.rules.push((selector.into(), redaction.into()));
+        self
+    }
+
+    pub(crate) fn apply(&self, value: &mut Content) -> Result<(), Error> {
+        for (selector, redaction) in &self.rules {
+            let segments = parse_selector(selector)?;
+            redact(value, &segments, redaction);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Redactions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.rules.iter().map(|(selector, _)| selector)).finish()
+    }
+}
+
+pub enum Redaction {
+    Static(Value),
+    Dynamic(Box<dyn Fn(&Value) -> Value + Send + Sync>),
+}
+
+impl<T: Into<Value>> From<T> for Redaction {
+    fn from(value: T) -> Self {
+        Redaction::Static(value.into())
+    }
+}
+
+/// Creates a redaction that replaces each selected value with the result of `f`.
+pub fn dynamic_redaction(f: impl Fn(&Value) -> Value + Send + Sync + 'static) -> Redaction {
+    Redaction::Dynamic(Box::new(f))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    AnyKey,
+    Index(usize),
+    AnyIndex,
+    Deep,
+}
+
+fn parse_selector(selector: &str) -> Result<Vec<Segment>, Error> {
+    let invalid = |message: &str| Error::Selector(format!("`{}`: {}", selector, message));
+
+    let mut segments = Vec::new();
+    let mut rest = selector;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '.' if rest.starts_with("**") => {
+                segments.push(Segment::Deep);
+                rest = &rest[2..];
+            }
+            '.' if rest.starts_with('*') => {
+                segments.push(Segment::AnyKey);
+                rest = &rest[1..];
+            }
+            '.' => {
+                let end = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(invalid("expected a key after `.`"));
+                }
+                segments.push(Segment::Key(rest[..end].to_owned()));
+                rest = &rest[end..];
+            }
+            '[' => {
+                let end = if let Some(quoted) = rest.strip_prefix('"') {
+                    quoted.find('"').map(|end| end + 2)
+                } else {
+                    rest.find(']')
+                };
+                let end = end
+                    .filter(|&end| rest[end..].starts_with(']'))
+                    .ok_or_else(|| invalid("unclosed `[`"))?;
+                let inner = &rest[..end];
+                segments.push(if inner.is_empty() {
+                    Segment::AnyIndex
+                } else if let Some(key) = inner.strip_prefix('"') {
+                    Segment::Key(key[..key.len() - 1].to_owned())
+                } else {
+                    Segment::Index(inner.parse().map_err(|_| invalid("expected an index inside `[]`"))?)
+                });
+                rest = &rest[end + 1..];
+            }
+            _ => return Err(invalid("expected `.` or `[`")),
+        }
+    }
+    Ok(segments)
+}
+
+fn redact(value: &mut Content, segments: &[Segment], redaction: &Redaction) {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => {
+            *value = replacement(value, redaction);
+            return;
+        }
+    };
+    if let Segment::Deep = segment {
+        redact(value, rest, redaction);
+        for child in value.children_mut() {
+            redact(child, segments, redaction);
+        }
+        return;
+    }
+    match (segment, unwrap_newtypes(value)) {
+        (Segment::Key(key), Content::Map(entries)) => {
+            let child = entries.iter_mut().find(|(name, _)| matches!(name, Content::String(name) if name == key));
+            if let Some((_, child)) = child {
+                redact(child, rest, redaction);
+            }
+        }
+        (Segment::Key(key), Content::Struct(_, fields))
+        | (Segment::Key(key), Content::StructVariant(_, _, _, fields)) => {
+            if let Some((_, child)) = fields.iter_mut().find(|(name, _)| name == key) {
+                redact(child, rest, redaction);
+            }
+        }
+        (Segment::AnyKey, value @ Content::Map(_))
+        | (Segment::AnyKey, value @ Content::Struct(..))
+        | (Segment::AnyKey, value @ Content::StructVariant(..)) => {
+            value.children_mut().into_iter().for_each(|child| redact(child, rest, redaction));
+        }
+        (Segment::Index(index), value) => {
+            if let Some(child) = items(value).and_then(|items| items.get_mut(*index)) {
+                redact(child, rest, redaction);
+            }
+        }
+        (Segment::AnyIndex, value) => {
+            if let Some(items) = items(value) {
+                items.iter_mut().for_each(|child| redact(child, rest, redaction));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Selectors look through options and newtype structs, as they do in the JSON representation.
+fn unwrap_newtypes(value: &mut Content) -> &mut Content {
+    match value {
+        Content::Some(inner) | Content::NewtypeStruct(_, inner) => unwrap_newtypes(inner),
+        value => value,
+    }
+}
+
+fn items(value: &mut Content) -> Option<&mut Vec<Content>> {
+    match value {
+        Content::Seq(items)
+        | Content::Tuple(items)
+        | Content::TupleStruct(_, items)
+        | Content::TupleVariant(_, _, _, items) => Some(items),
+        _ => None,
+    }
+}
+
+/// Dynamic redactions see the JSON representation of the redacted value, or `null` if it has
+/// none (e.g. a map with non-string keys).
+fn replacement(value: &Content, redaction: &Redaction) -> Content {
+    let replacement = match redaction {
+        Redaction::Static(replacement) => return Content::new(replacement).unwrap_or(Content::Unit),
+        Redaction::Dynamic(f) => f(&serde_json::to_value(value).unwrap_or(Value::Null)),
+    };
+    Content::new(&replacement).unwrap_or(Content::Unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::content::Content;
+
+    use super::{dynamic_redaction, parse_selector, Redactions, Segment};
+
+    #[test]
+    fn selectors() {
+        assert_eq!(
+            parse_selector(r#".users[].created_at[2]["a b"].*.**"#).unwrap(),
+            vec![
+                Segment::Key("users".into()),
+                Segment::AnyIndex,
+                Segment::Key("created_at".into()),
+                Segment::Index(2),
+                Segment::Key("a b".into()),
+                Segment::AnyKey,
+                Segment::Deep,
+            ]
+        );
+        parse_selector("users").unwrap_err();
+        parse_selector(".users[").unwrap_err();
+        parse_selector(".users[x]").unwrap_err();
+        parse_selector(".").unwrap_err();
+    }
+
+    #[test]
+    fn redact() {
+        let mut value = Content::new(&json!({
+            "id": 1,
+            "users": [
+                { "id": 2, "name": "a", "created_at": "2021-04-01" },
+                { "id": 3, "name": "b", "created_at": "2021-04-02" },
+            ],
+        }))
+        .unwrap();
+        Redactions::new()
+            .add(".users[].created_at", "[date]")
+            .add(".**.id", dynamic_redaction(|value| (value.as_u64().unwrap() > 0).into()))
+            .apply(&mut value)
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!({
+                "id": true,
+                "users": [
+                    { "id": true, "name": "a", "created_at": "[date]" },
+                    { "id": true, "name": "b", "created_at": "[date]" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn redact_structs() {
+        #[derive(serde::Serialize)]
+        struct Session {
+            user: Option<(u32, &'static str)>,
+            token: &'static str,
+        }
+
+        let mut value = Content::new(&Session {
+            user: Some((7, "ferris")),
+            token: "secret",
+        })
+        .unwrap();
+        Redactions::new().add(".user[0]", 0).add(".token", "[token]").apply(&mut value).unwrap();
+        assert_eq!(serde_json::to_value(&value).unwrap(), json!({ "user": [0, "ferris"], "token": "[token]" }));
+    }
+}
@@ -0,0 +1,240 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::content::Content;
+use crate::{check_snapshot, Error, Redactions};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "ron")]
+    Ron,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => "json",
+            #[cfg(feature = "yaml")]
+            Format::Yaml => "yaml",
+            #[cfg(feature = "toml")]
+            Format::Toml => "toml",
+            #[cfg(feature = "ron")]
+            Format::Ron => "ron",
+        }
+    }
+
+    fn serialize(self, value: &Content) -> Result<String, Error> {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::to_string_pretty(value).map_err(serialize_error),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::to_string(value).map_err(serialize_error),
+            #[cfg(feature = "toml")]
+            Format::Toml => {
+                // Going through `toml::Value` emits plain values before tables, as TOML requires.
+                let value = toml::Value::try_from(value).map_err(serialize_error)?;
+                toml::to_string_pretty(&value).map_err(serialize_error)
+            }
+            #[cfg(feature = "ron")]
+            Format::Ron => {
+                let config = ron::ser::PrettyConfig::new().struct_names(true);
+                ron::ser::to_string_pretty(value, config).map_err(serialize_error)
+            }
+        }
+    }
+}
+
+pub fn check_serialized_snapshot(
+    actual: &impl Serialize,
+    format: Format,
+    snapshot: impl AsRef<Path>,
+) -> Result<(), Error> {
+    check_redacted_snapshot(actual, format, &Redactions::new(), snapshot)
+}
+
+pub fn check_redacted_snapshot(
+    actual: &impl Serialize,
+    format: Format,
+    redactions: &Redactions,
+    snapshot: impl AsRef<Path>,
+) -> Result<(), Error> {
+    check_snapshot(&serialize(actual, format, redactions)?, snapshot)
+}
+
+#[cfg(feature = "json")]
+pub fn check_json_snapshot(actual: &impl Serialize, snapshot: impl AsRef<Path>) -> Result<(), Error> {
+    check_serialized_snapshot(actual, Format::Json, snapshot)
+}
+
+#[cfg(feature = "yaml")]
+pub fn check_yaml_snapshot(actual: &impl Serialize, snapshot: impl AsRef<Path>) -> Result<(), Error> {
+    check_serialized_snapshot(actual, Format::Yaml, snapshot)
+}
+
+#[cfg(feature = "toml")]
+pub fn check_toml_snapshot(actual: &impl Serialize, snapshot: impl AsRef<Path>) -> Result<(), Error> {
+    check_serialized_snapshot(actual, Format::Toml, snapshot)
+}
+
+#[cfg(feature = "ron")]
+pub fn check_ron_snapshot(actual: &impl Serialize, snapshot: impl AsRef<Path>) -> Result<(), Error> {
+    check_serialized_snapshot(actual, Format::Ron, snapshot)
+}
+
+/// Serializes `value` in `format`. Maps are sorted by key, so that the output does not depend on
+/// e.g. `HashMap` iteration order.
+pub(crate) fn serialize(value: &impl Serialize, format: Format, redactions: &Redactions) -> Result<String, Error> {
+    let mut value = Content::new(value).map_err(serialize_error)?;
+    redactions.apply(&mut value)?;
+    let mut serialized = format.serialize(&value)?;
+    if !serialized.ends_with('\n') {
+        serialized.push('\n');
+    }
+    Ok(serialized)
+}
+
+fn serialize_error(err: impl std::fmt::Display) -> Error {
+    Error::Serialize(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct User {
+        name: &'static str,
+        roles: Vec<&'static str>,
+        settings: HashMap<&'static str, u32>,
+    }
+
+    fn user() -> User {
+        let mut settings = HashMap::new();
+        settings.insert("volume", 11);
+        settings.insert("brightness", 3);
+        settings.insert("contrast", 7);
+        User {
+            name: "ferris",
+            roles: vec!["admin", "crab"],
+            settings,
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_snapshot() {
+        super::check_json_snapshot(&user(), "snapshots/serialized.json").unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn redacted_snapshot() {
+        let redactions = super::Redactions::new().add(".settings.*", 0).add(".roles[1]", "[role]");
+        super::check_redacted_snapshot(&user(), super::Format::Json, &redactions, "snapshots/redacted.json")
+            .unwrap();
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_snapshot() {
+        super::check_yaml_snapshot(&user(), "snapshots/serialized.yaml").unwrap();
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_snapshot() {
+        super::check_toml_snapshot(&user(), "snapshots/serialized.toml").unwrap();
+    }
+
+    #[cfg(feature = "ron")]
+    #[test]
+    fn ron_snapshot() {
+        super::check_ron_snapshot(&user(), "snapshots/serialized.ron").unwrap();
+    }
+
+    #[cfg(any(feature = "json", feature = "yaml", feature = "ron"))]
+    #[derive(Serialize)]
+    enum Shape {
+        Point,
+        Circle(u32),
+        Line(u32, u32),
+        Rect { width: u32, height: u32 },
+    }
+
+    #[cfg(any(feature = "json", feature = "yaml", feature = "ron"))]
+    #[derive(Serialize)]
+    struct Drawing {
+        title: Option<&'static str>,
+        shapes: Vec<Shape>,
+        layers: HashMap<u32, &'static str>,
+    }
+
+    #[cfg(any(feature = "json", feature = "yaml", feature = "ron"))]
+    fn drawing() -> Drawing {
+        let mut layers = HashMap::new();
+        layers.insert(10, "top");
+        layers.insert(2, "bottom");
+        Drawing {
+            title: None,
+            shapes: vec![Shape::Point, Shape::Circle(1), Shape::Line(2, 3), Shape::Rect { width: 4, height: 5 }],
+            layers,
+        }
+    }
+
+    fn serialize(value: &impl Serialize, format: super::Format) -> String {
+        super::serialize(value, format, &super::Redactions::new()).unwrap()
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_data_model() {
+        let json = serialize(&drawing(), super::Format::Json);
+        assert!(json.contains("\"title\": null"), "{}", json);
+        assert!(json.contains("{\n    \"2\": \"bottom\",\n    \"10\": \"top\"\n  }"), "{}", json);
+        assert!(json.contains("\"Line\": [\n        2,\n        3\n      ]"), "{}", json);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_data_model() {
+        let yaml = serialize(&drawing(), super::Format::Yaml);
+        assert!(yaml.contains("title: ~\n"), "{}", yaml);
+        assert!(yaml.contains("layers:\n  2: bottom\n  10: top\n"), "{}", yaml);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_data_model() {
+        #[derive(Serialize)]
+        struct Config {
+            name: &'static str,
+            description: Option<&'static str>,
+        }
+
+        let config = Config {
+            name: "x",
+            description: None,
+        };
+        assert_eq!(serialize(&config, super::Format::Toml), "name = 'x'\n");
+    }
+
+    #[cfg(feature = "ron")]
+    #[test]
+    fn ron_data_model() {
+        let ron = serialize(&drawing(), super::Format::Ron);
+        assert!(ron.starts_with("Drawing(\n    title: None,"), "{}", ron);
+        assert!(ron.contains("Line(2, 3)"), "{}", ron);
+        assert!(ron.contains("Rect(\n            width: 4,"), "{}", ron);
+        assert!(ron.contains("2: \"bottom\",\n        10: \"top\","), "{}", ron);
+    }
+}
@@ -0,0 +1,219 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use crate::config;
+use crate::{CompareOptions, DiffOptions, Filters, Normalize, UPDATE_SNAPSHOTS_VAR};
+
+thread_local! {
+    static CURRENT: RefCell<Option<Settings>> = const { RefCell::new(None) };
+}
+
+/// When snapshot files are written. The default is `New`, or `No` when the `CI` environment
+/// variable is set, so that a forgotten snapshot fails the build rather than being written on
+/// the CI runner. On CI, only `UPDATE_SNAPSHOTS` overrides this, not the project configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateMode {
+    /// Create missing snapshots and overwrite differing ones. Set by `UPDATE_SNAPSHOTS=always`,
+    /// or `UPDATE_SNAPSHOTS=1`.
+    Always,
+    /// Create missing snapshots, but don't update existing ones. Set by `UPDATE_SNAPSHOTS=new`.
+    New,
+    /// Never write snapshots, and fail when a snapshot is missing. Set by `UPDATE_SNAPSHOTS=no`.
+    No,
+    /// Write new or differing snapshots to pending `.new` files, to be reviewed and accepted
+    /// later. Set by `UPDATE_SNAPSHOTS=pending`.
+    Pending,
+}
+
+impl Default for UpdateMode {
+    fn default() -> Self {
+        if on_ci() {
+            UpdateMode::No
+        } else {
+            UpdateMode::New
+        }
+    }
+}
+
+fn on_ci() -> bool {
+    std::env::var("CI").is_ok_and(|ci| !ci.is_empty() && ci != "0" && ci != "false")
+}
+
+impl UpdateMode {
+    /// Parses `always`, `new`, `no` or `pending`.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "always" => Some(UpdateMode::Always),
+            "new" => Some(UpdateMode::New),
+            "no" => Some(UpdateMode::No),
+            "pending" => Some(UpdateMode::Pending),
+            _ => None,
+        }
+    }
+
+    /// Reads `UPDATE_SNAPSHOTS`, which also accepts `1`, `true` and `yes` for `always`. An
+    /// unknown mode is only warned about once per process.
+    fn from_env() -> Option<Self> {
+        static WARNED: Once = Once::new();
+        let value = std::env::var(UPDATE_SNAPSHOTS_VAR).ok()?;
+        match value.as_str() {
+            "1" | "true" | "yes" => Some(UpdateMode::Always),
+            _ => UpdateMode::parse(&value).or_else(|| {
+                WARNED.call_once(|| eprintln!("Ignoring unknown {} mode `{}`", UPDATE_SNAPSHOTS_VAR, value));
+                None
+            }),
+        }
+    }
+
+    /// The mode for the `configured` one: `explicit`, from `UPDATE_SNAPSHOTS`, if it is set, or
+    /// else `No` on CI, where the configuration may not write snapshots.
+    fn resolve(configured: UpdateMode, explicit: Option<UpdateMode>, ci: bool) -> Self {
+        match explicit {
+            Some(mode) => mode,
+            None if ci => UpdateMode::No,
+            None => configured,
+        }
+    }
+}
+
+/// Settings for the snapshot assertions made on the current thread. Unless settings are bound
+/// with `bind` or `bind_to_thread`, they are read from the environment on each assertion.
+///
+/// ```no_run
+/// use snapshot_testing::{assert_snapshot, Settings, UpdateMode};
+///
+/// Settings::current().update_mode(UpdateMode::Always).bind(|| {
+///     assert_snapshot!("value");
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub(crate) snapshot_dir: PathBuf,
+    pub(crate) update_mode: UpdateMode,
+    pub(crate) compare: CompareOptions,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            snapshot_dir: PathBuf::from("snapshots"),
+            update_mode: UpdateMode::default(),
+            compare: CompareOptions::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// The settings of the project configuration (see `CONFIG_FILE`), with the update mode and
+    /// diff options overridden by `UPDATE_SNAPSHOTS` and `SNAPSHOT_DIFF` when they are set. On
+    /// CI, the update mode is `No` unless `UPDATE_SNAPSHOTS` is set.
+    pub fn from_env() -> Self {
+        let mut settings = config::project_settings().clone();
+        settings.update_mode = UpdateMode::resolve(settings.update_mode, UpdateMode::from_env(), on_ci());
+        settings.compare.diff = settings.compare.diff.with_env();
+        settings
+    }
+
+    /// The settings bound to the current thread, or else `Settings::from_env()`.
+    pub fn current() -> Self {
+        with_current(Settings::clone)
+    }
+
+    /// The directory of snapshots named by the assertion macros. A relative directory is
+    /// relative to the crate's manifest directory. Defaults to `snapshots`.
+    pub fn snapshot_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.snapshot_dir = dir.into();
+        self
+    }
+
+    pub fn update_mode(mut self, mode: UpdateMode) -> Self {
+        self.update_mode = mode;
+        self
+    }
+
+    pub fn diff_options(mut self, options: DiffOptions) -> Self {
+        self.compare.diff = options;
+        self
+    }
+
+    /// The filters applied to text snapshots. Checks given their own options apply their filters
+    /// after these.
+    pub fn filters(mut self, filters: Filters) -> Self {
+        self.compare.filters = filters;
+        self
+    }
+
+    pub fn normalize(mut self, normalize: Normalize) -> Self {
+        self.compare.normalize = normalize;
+        self
+    }
+
+    /// The options text snapshots are compared with.
+    pub fn compare_options(&self) -> &CompareOptions {
+        &self.compare
+    }
+
+    /// Runs `f` with these settings bound to the current thread, restoring the previous
+    /// settings afterwards, even if `f` panics.
+    pub fn bind<R>(self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Settings>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|current| current.replace(Some(self))));
+        f()
+    }
+
+    /// Binds these settings to the current thread for the rest of its life, or until they are
+    /// replaced.
+    pub fn bind_to_thread(self) {
+        CURRENT.with(|current| *current.borrow_mut() = Some(self));
+    }
+
+    pub(crate) fn snapshot_path(&self, manifest_dir: &str, file_name: &str) -> PathBuf {
+        Path::new(manifest_dir).join(&self.snapshot_dir).join(file_name)
+    }
+}
+
+/// Calls `f` with the current settings, without cloning them.
+pub(crate) fn with_current<R>(f: impl FnOnce(&Settings) -> R) -> R {
+    CURRENT.with(|current| match &*current.borrow() {
+        Some(settings) => f(settings),
+        None => f(&Settings::from_env()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Settings, UpdateMode};
+
+    #[test]
+    fn bind() {
+        let always = Settings::default().update_mode(UpdateMode::Always);
+        let mode = always.bind(|| {
+            let inner = Settings::current().update_mode(UpdateMode::Pending).bind(Settings::current);
+            assert_eq!(inner.update_mode, UpdateMode::Pending);
+            Settings::current().update_mode
+        });
+        assert_eq!(mode, UpdateMode::Always);
+        assert_eq!(UpdateMode::parse("no"), Some(UpdateMode::No));
+        assert_eq!(UpdateMode::parse("sometimes"), None);
+        assert_eq!(UpdateMode::resolve(UpdateMode::Always, None, true), UpdateMode::No);
+        assert_eq!(UpdateMode::resolve(UpdateMode::Always, None, false), UpdateMode::Always);
+        assert_eq!(UpdateMode::resolve(UpdateMode::New, Some(UpdateMode::Always), true), UpdateMode::Always);
+
+        std::thread::spawn(|| {
+            Settings::default().snapshot_dir("/tests/snaps").bind_to_thread();
+            let path = Settings::current().snapshot_path("/crate", "a.snap");
+            assert_eq!(path, std::path::Path::new("/tests/snaps/a.snap"));
+        })
+        .join()
+        .unwrap();
+        assert_eq!(Settings::current().snapshot_dir, std::path::Path::new("snapshots"));
+    }
+}